@@ -1,7 +1,7 @@
-import init, { random_start } from '../../../../packages/wasm-bindings/pkg/luzhanqi_wasm.js';
+import init, { board_geometry, random_start } from '../../../../packages/wasm-bindings/pkg/luzhanqi_wasm.js';
 
 export async function initWasm() {
     await init();
 }
 
-export { random_start };
+export { board_geometry, random_start };
@@ -0,0 +1,203 @@
+//! What a player can infer about the opponent's hidden pieces.
+//!
+//! Everything comes from the player's [`PlayerView`]:
+//! - combat reveals or removes every piece it involves, so revealed and
+//!   captured pieces come off the counts the rules give each side;
+//! - a piece that has moved is not a landmine or the flag, and one that made
+//!   a move no other piece can make, turning a corner on the railroad or
+//!   leaving its line, is an engineer;
+//! - each piece started where `history` traces it back to, so it must be a
+//!   type the placement rules allow there: landmines on the back two rows,
+//!   the flag in a headquarters, no bombs on the first row.
+//!
+//! The constraints are combined by dealing the hidden pieces out many times
+//! with the same [`Determinizer`] information set search uses, and counting
+//! how often each piece gets each type.
+
+use crate::ismcts::{seeded_rng, Determinizer};
+use crate::Engine;
+use game::board::{Color, PieceType, SquareIndex, LIST_OF_PIECETYPES, NUM_PIECETYPES};
+use game::geometry::{ADJACENT, RAIL_LINES};
+use game::movegen::Move;
+use game::view::PlayerView;
+use serde::{Deserialize, Serialize};
+
+/// How many deals [`Engine::beliefs`] counts.
+pub const DEFAULT_BELIEF_SAMPLES: u32 = 200;
+
+/// Whether only an engineer could have made `mv`: it is neither a step along
+/// a road nor a straight run along one railroad line.
+pub fn needs_engineer(mv: Move) -> bool {
+    !ADJACENT[mv.from as usize].contains(mv.to)
+        && !RAIL_LINES
+            .iter()
+            .any(|line| line.contains(&mv.from) && line.contains(&mv.to))
+}
+
+/// What one hidden piece is likely to be.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PieceBelief {
+    pub square: SquareIndex,
+    /// The probability of each type, indexed by `PieceType as usize`.
+    pub probabilities: [f64; NUM_PIECETYPES],
+}
+
+impl PieceBelief {
+    pub fn probability(&self, ty: PieceType) -> f64 {
+        self.probabilities[ty as usize]
+    }
+
+    /// The most probable type, for showing a likely piece on the board.
+    pub fn most_likely(&self) -> PieceType {
+        LIST_OF_PIECETYPES
+            .into_iter()
+            .max_by(|&a, &b| self.probability(a).total_cmp(&self.probability(b)))
+            .unwrap()
+    }
+}
+
+/// One player's beliefs about every hidden opponent piece.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Beliefs {
+    pub perspective: Color,
+    /// One entry per hidden piece, in square order.
+    pub pieces: Vec<PieceBelief>,
+}
+
+impl Beliefs {
+    /// Estimates the beliefs from `samples` random deals that agree with
+    /// `view`. If no deal agrees, which only happens when the view breaks
+    /// the rules, each piece is judged on its own instead.
+    pub fn from_view(view: &PlayerView, samples: u32, seed: u64) -> Self {
+        let determinizer = Determinizer::new(view);
+        let hidden = &determinizer.hidden;
+        let mut rng = seeded_rng(seed);
+        let mut tally = vec![[0.0; NUM_PIECETYPES]; hidden.len()];
+        let mut dealt = 0;
+        for _ in 0..samples {
+            if let Some(types) = determinizer.deal(&mut rng) {
+                dealt += 1;
+                for (counts, ty) in tally.iter_mut().zip(types) {
+                    counts[ty as usize] += 1.0;
+                }
+            }
+        }
+        if dealt == 0 {
+            // Weigh each type a piece allows by how many are left
+            for (counts, piece) in tally.iter_mut().zip(hidden) {
+                for ty in LIST_OF_PIECETYPES {
+                    if determinizer.allows(piece, ty) {
+                        counts[ty as usize] = determinizer.remaining[ty as usize] as f64;
+                    }
+                }
+            }
+        }
+
+        let pieces = tally
+            .into_iter()
+            .zip(hidden)
+            .map(|(counts, piece)| {
+                let total: f64 = counts.iter().sum();
+                PieceBelief {
+                    square: piece.square,
+                    probabilities: counts.map(|n| if total > 0.0 { n / total } else { 0.0 }),
+                }
+            })
+            .collect();
+        Self {
+            perspective: view.perspective,
+            pieces,
+        }
+    }
+
+    /// The belief about the piece on `sq`, if it is hidden.
+    pub fn get(&self, sq: SquareIndex) -> Option<&PieceBelief> {
+        self.pieces.iter().find(|b| b.square == sq)
+    }
+}
+
+impl Engine {
+    /// What `perspective` can infer about the opponent's hidden pieces.
+    pub fn beliefs(&self, perspective: Color) -> Beliefs {
+        Beliefs::from_view(
+            &self.state.view(perspective),
+            DEFAULT_BELIEF_SAMPLES,
+            self.state.ply as u64,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use game::board::{sq, GameState, Piece};
+    use game::rules::Rules;
+
+    #[test]
+    fn test_engineer_moves() {
+        assert!(!needs_engineer(Move::new(sq::B1, sq::B5)));
+        assert!(!needs_engineer(Move::new(sq::F3, sq::H3)));
+        assert!(!needs_engineer(Move::new(sq::D3, sq::C4)));
+        assert!(needs_engineer(Move::new(sq::B3, sq::D1)));
+        assert!(needs_engineer(Move::new(sq::F2, sq::H4)));
+    }
+
+    #[test]
+    fn test_beliefs_at_the_start() {
+        let state = GameState::random_start(6);
+        let beliefs = Engine::with_state(state.clone()).beliefs(Color::Red);
+        assert_eq!(beliefs.pieces.len(), 25);
+        for belief in &beliefs.pieces {
+            let sum: f64 = belief.probabilities.iter().sum();
+            assert!((sum - 1.0).abs() < 1e-9);
+            // The flag can only be in a headquarters
+            let flag = belief.probability(PieceType::Flag);
+            assert_eq!(flag > 0.0, sq::BLACK_HQ.contains(&belief.square));
+            if !sq::BLACK_BACK_ROWS.contains(&belief.square) {
+                assert_eq!(belief.probability(PieceType::Landmine), 0.0);
+            }
+        }
+        assert!(beliefs
+            .get(state.flag_square(Color::Red).unwrap())
+            .is_none());
+    }
+
+    #[test]
+    fn test_railroad_turn_reveals_engineer() {
+        let mut piece_counts = [0; NUM_PIECETYPES];
+        for ty in [PieceType::Flag, PieceType::Army, PieceType::Engineer] {
+            piece_counts[ty as usize] = 1;
+        }
+        let mut state = GameState::with_rules(Rules {
+            piece_counts,
+            ..Rules::default()
+        });
+        for (square, ty, color) in [
+            (sq::A2, PieceType::Flag, Color::Red),
+            (sq::B3, PieceType::Army, Color::Red),
+            (sq::M2, PieceType::Flag, Color::Black),
+            (sq::M4, PieceType::Army, Color::Black),
+            (sq::L1, PieceType::Engineer, Color::Black),
+        ] {
+            state.set_piece(square, Some(Piece { ty, color }));
+        }
+        state.set_turn(Color::Black);
+        // Down the side column and along the front row
+        state.make_move(Move::new(sq::L1, sq::H2)).unwrap();
+
+        let beliefs = Beliefs::from_view(&state.view(Color::Red), 50, 1);
+        let engineer = beliefs.get(sq::H2).unwrap();
+        assert_eq!(engineer.most_likely(), PieceType::Engineer);
+        assert_eq!(engineer.probability(PieceType::Engineer), 1.0);
+        // The other two pieces share the flag and the army between them
+        let flag = beliefs.get(sq::M2).unwrap().probability(PieceType::Flag);
+        assert!(flag > 0.0 && flag < 1.0);
+        assert_eq!(
+            beliefs
+                .get(sq::M4)
+                .unwrap()
+                .probability(PieceType::Engineer),
+            0.0
+        );
+    }
+}
@@ -0,0 +1,201 @@
+//! Static evaluation.
+//!
+//! A position is scored as the sum of a few terms for each side, Red's total
+//! less Black's, then turned around for the side to move:
+//! - material, by [`PieceType::rank`], with separate values for engineers,
+//!   bombs and landmines, which rank does not describe;
+//! - flag safety: own pieces next to the flag count for it, enemy pieces
+//!   next to it count against it;
+//! - frontline control: pieces on either end of the crossings at G1, G3 and
+//!   G5, ready to cross;
+//! - pieces sitting in a camp, where they cannot be attacked;
+//! - engineer mobility: the number of squares each engineer can reach along
+//!   the railroad.
+//!
+//! All of the weights are in [`EvalWeights`], which can be loaded from any
+//! serde format to tune the engine without recompiling.
+
+use game::bitboard::{Bitboard, CAMPS, FRONTLINE};
+use game::board::{Color, GameState, Piece, PieceType};
+use game::geometry::ADJACENT;
+use serde::{Deserialize, Serialize};
+
+/// The weights of each evaluation term. Fields missing from a serialized
+/// config keep their default values.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(default)]
+pub struct EvalWeights {
+    /// The value of each ranked piece from the platoon (rank 2) up to the
+    /// overall (rank 9).
+    pub rank_values: [i32; 8],
+    pub engineer: i32,
+    pub bomb: i32,
+    pub landmine: i32,
+    /// For each own piece next to the flag.
+    pub flag_guard: i32,
+    /// Against each enemy piece next to the flag.
+    pub flag_threat: i32,
+    /// For each piece on either end of a frontline crossing.
+    pub frontline: i32,
+    /// For each piece in a camp.
+    pub camp: i32,
+    /// For each square an engineer can reach along the railroad.
+    pub engineer_mobility: i32,
+}
+
+impl Default for EvalWeights {
+    fn default() -> Self {
+        Self {
+            rank_values: [80, 120, 180, 250, 350, 500, 800, 1000],
+            engineer: 150,
+            bomb: 400,
+            landmine: 200,
+            flag_guard: 15,
+            flag_threat: 60,
+            frontline: 20,
+            camp: 15,
+            engineer_mobility: 3,
+        }
+    }
+}
+
+/// The squares either side of the frontline crossings.
+fn frontline_approaches() -> Bitboard {
+    FRONTLINE
+        .squares()
+        .flat_map(|sq| ADJACENT[sq as usize].iter())
+        .collect()
+}
+
+impl EvalWeights {
+    /// The material value of a piece.
+    pub fn piece_value(&self, ty: PieceType) -> i32 {
+        match ty {
+            PieceType::Engineer => self.engineer,
+            PieceType::Bomb => self.bomb,
+            PieceType::Landmine => self.landmine,
+            PieceType::Flag => 0,
+            _ => self.rank_values[ty.rank() - 2],
+        }
+    }
+
+    /// The score of `state` from the point of view of the side to move.
+    pub fn evaluate(&self, state: &GameState) -> i32 {
+        let score = self.side_score(state, Color::Red) - self.side_score(state, Color::Black);
+        match state.turn {
+            Color::Red => score,
+            Color::Black => -score,
+        }
+    }
+
+    /// The terms for `color` alone.
+    fn side_score(&self, state: &GameState, color: Color) -> i32 {
+        let occupancy = state.occupancy();
+        let own = occupancy.color(color);
+        let mut score = 0;
+
+        for sq in own.squares() {
+            score += self.piece_value(state.board[sq as usize].unwrap().ty);
+        }
+
+        let flag = occupancy.piece(Piece {
+            ty: PieceType::Flag,
+            color,
+        });
+        if let Some(flag) = flag.first() {
+            let around: Bitboard = ADJACENT[flag as usize].iter().collect();
+            score += self.flag_guard * (around & own).count() as i32;
+            score -= self.flag_threat * (around & occupancy.color(color.other())).count() as i32;
+        }
+
+        score += self.frontline * (own & frontline_approaches()).count() as i32;
+        score += self.camp * (own & CAMPS).count() as i32;
+
+        let engineers = occupancy.piece(Piece {
+            ty: PieceType::Engineer,
+            color,
+        });
+        for sq in engineers.squares() {
+            score += self.engineer_mobility * state.rail_reach(sq).destinations().count() as i32;
+        }
+        score
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use game::board::{sq, SquareIndex};
+
+    fn state_with(pieces: &[(SquareIndex, PieceType, Color)]) -> GameState {
+        let mut state = GameState::new();
+        for &(square, ty, color) in pieces {
+            state.set_piece(square, Some(Piece { ty, color }));
+        }
+        state
+    }
+
+    #[test]
+    fn test_score_is_for_side_to_move() {
+        let weights = EvalWeights::default();
+        let mut state = GameState::random_start(5);
+        let red = weights.evaluate(&state);
+        state.set_turn(Color::Black);
+        assert_eq!(weights.evaluate(&state), -red);
+    }
+
+    #[test]
+    fn test_material_by_rank() {
+        let weights = EvalWeights::default();
+        assert_eq!(weights.piece_value(PieceType::Overall), 1000);
+        assert_eq!(weights.piece_value(PieceType::Platoon), 80);
+        assert_eq!(weights.piece_value(PieceType::Engineer), 150);
+        assert_eq!(weights.piece_value(PieceType::Flag), 0);
+    }
+
+    #[test]
+    fn test_positional_terms() {
+        let weights = EvalWeights {
+            rank_values: [0; 8],
+            engineer: 0,
+            bomb: 0,
+            landmine: 0,
+            flag_guard: 1,
+            flag_threat: 10,
+            frontline: 100,
+            camp: 1000,
+            engineer_mobility: 0,
+        };
+        let state = state_with(&[
+            (sq::A2, PieceType::Flag, Color::Red),
+            (sq::A1, PieceType::Landmine, Color::Red),
+            (sq::B2, PieceType::Platoon, Color::Black),
+            (sq::F1, PieceType::Company, Color::Red),
+            (sq::D3, PieceType::Company, Color::Red),
+            (sq::M2, PieceType::Flag, Color::Black),
+        ]);
+        assert_eq!(weights.evaluate(&state), 1 - 10 + 100 + 1000);
+    }
+
+    #[test]
+    fn test_engineer_mobility() {
+        let weights = EvalWeights {
+            engineer_mobility: 1,
+            ..EvalWeights::default()
+        };
+        let base = state_with(&[(sq::C3, PieceType::Engineer, Color::Red)]);
+        let state = state_with(&[(sq::B1, PieceType::Engineer, Color::Red)]);
+        let reach = state.rail_reach(sq::B1).destinations().count() as i32;
+        assert!(reach > 0);
+        assert_eq!(weights.evaluate(&state) - weights.evaluate(&base), reach);
+    }
+
+    #[test]
+    fn test_weights_from_partial_config() {
+        let weights: EvalWeights =
+            serde_json::from_str(r#"{ "camp": 40, "engineer": 200 }"#).unwrap();
+        assert_eq!(weights.camp, 40);
+        assert_eq!(weights.engineer, 200);
+        assert_eq!(weights.bomb, EvalWeights::default().bomb);
+    }
+}
@@ -0,0 +1,588 @@
+//! Information set Monte Carlo tree search, for play without seeing the
+//! opponent's pieces.
+//!
+//! The search is given a single player's [`PlayerView`]. Each iteration
+//! deals the opponent's hidden pieces out at random in a way that agrees with
+//! everything the view shows (a *determinization*), then walks one shared
+//! tree of moves, only following moves that are legal in that deal. New
+//! positions are scored by a random playout, cut short after
+//! [`IsmctsConfig::max_playout_plies`] and scored with the static evaluation.
+//! A deal has no history, so the repetition limits cannot be checked in it;
+//! the moves at the root are instead limited to the ones the real game
+//! allows, as given by [`GameState::allowed_moves`].
+//!
+//! A deal agrees with the view when
+//! - the opponent has the right number of each piece type, after taking off
+//!   its captured and revealed pieces;
+//! - each hidden piece could have started the game where `history` says it
+//!   did, under the same placement rules [`GameState::is_startpos_legal`]
+//!   checks;
+//! - no hidden piece that has moved is a landmine or the flag, and a piece
+//!   that made a move only an engineer can make is an engineer.
+
+use crate::belief::needs_engineer;
+use crate::evaluation::EvalWeights;
+use crate::limits::SearchLimits;
+use crate::Engine;
+use game::board::{
+    Color, GameState, Piece, PieceType, SquareIndex, LIST_OF_PIECETYPES, NUM_PIECETYPES,
+};
+use game::movegen::Move;
+use game::prng::PseudoRng;
+use game::setup::can_start_on;
+use game::view::PlayerView;
+use serde::{Deserialize, Serialize};
+
+/// How many deals [`Determinizer::sample`] tries before giving up.
+const MAX_DEAL_ATTEMPTS: usize = 20;
+
+/// Types dealt first, because they have the fewest squares to choose from.
+/// The other types follow in [`LIST_OF_PIECETYPES`] order, which deals
+/// engineers last, so pieces that can only be engineers are still free.
+const DEAL_ORDER: [PieceType; 3] = [PieceType::Flag, PieceType::Landmine, PieceType::Bomb];
+
+/// A generator for `seed`. Xorshift never leaves the all-zero state, so the
+/// seed is mixed with a constant first.
+pub(crate) fn seeded_rng(seed: u64) -> PseudoRng {
+    PseudoRng::new(seed ^ 0x9e37_79b9_7f4a_7c15)
+}
+
+/// An opponent piece whose type the player does not know.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct HiddenPiece {
+    pub(crate) square: SquareIndex,
+    origin: SquareIndex,
+    moved: bool,
+    /// The piece has turned a corner on the railroad.
+    engineer: bool,
+}
+
+/// Deals out the opponent's hidden pieces in ways that agree with a view.
+#[derive(Debug, Clone)]
+pub struct Determinizer {
+    view: PlayerView,
+    pub(crate) hidden: Vec<HiddenPiece>,
+    /// How many hidden pieces of each type are left to deal, indexed by
+    /// `PieceType as usize`.
+    pub(crate) remaining: [usize; NUM_PIECETYPES],
+}
+
+impl Determinizer {
+    pub fn new(view: &PlayerView) -> Self {
+        let opponent = view.perspective.other();
+        let mut remaining = view.rules.piece_counts;
+        let known = view
+            .board
+            .iter()
+            .flatten()
+            .filter(|p| p.color == opponent)
+            .filter_map(|p| p.ty);
+        let captured = view
+            .captured
+            .iter()
+            .filter(|p| p.color == opponent)
+            .map(|p| p.ty);
+        for ty in known.chain(captured) {
+            remaining[ty as usize] = remaining[ty as usize].saturating_sub(1);
+        }
+
+        let hidden = view
+            .hidden_squares()
+            .into_iter()
+            .map(|square| {
+                let (origin, moved) = view.origin(square);
+                HiddenPiece {
+                    square,
+                    origin,
+                    moved,
+                    engineer: view.piece_moves(square).into_iter().any(needs_engineer),
+                }
+            })
+            .collect();
+
+        Self {
+            view: view.clone(),
+            hidden,
+            remaining,
+        }
+    }
+
+    /// Whether `piece` could be of type `ty`.
+    pub(crate) fn allows(&self, piece: &HiddenPiece, ty: PieceType) -> bool {
+        if piece.moved && matches!(ty, PieceType::Landmine | PieceType::Flag) {
+            return false;
+        }
+        if piece.engineer && ty != PieceType::Engineer {
+            return false;
+        }
+        let color = self.view.perspective.other();
+        can_start_on(&self.view.rules, Piece { ty, color }, piece.origin)
+    }
+
+    /// Deals one type to each hidden piece, or returns `None` if no deal
+    /// that agrees with the view was found.
+    pub(crate) fn deal(&self, rng: &mut PseudoRng) -> Option<Vec<PieceType>> {
+        if self.remaining.iter().sum::<usize>() != self.hidden.len() {
+            return None;
+        }
+        let order = DEAL_ORDER.into_iter().chain(
+            LIST_OF_PIECETYPES
+                .into_iter()
+                .filter(|ty| !DEAL_ORDER.contains(ty)),
+        );
+        let mut types: Vec<Option<PieceType>> = vec![None; self.hidden.len()];
+        for ty in order {
+            for _ in 0..self.remaining[ty as usize] {
+                let choices: Vec<usize> = (0..self.hidden.len())
+                    .filter(|&i| types[i].is_none() && self.allows(&self.hidden[i], ty))
+                    .collect();
+                if choices.is_empty() {
+                    return None;
+                }
+                types[choices[rng.gen_range(0..choices.len())]] = Some(ty);
+            }
+        }
+        types.into_iter().collect()
+    }
+
+    /// A full game that agrees with the view, with the hidden pieces dealt
+    /// at random. Returns `None` if no such game could be found, which
+    /// happens when the view contradicts the rules.
+    pub fn sample(&self, rng: &mut PseudoRng) -> Option<GameState> {
+        let types = (0..MAX_DEAL_ATTEMPTS).find_map(|_| self.deal(rng))?;
+        let view = &self.view;
+        let mut state = GameState::with_rules(view.rules);
+        for (sq, piece) in view.board.iter().enumerate() {
+            if let Some(ty) = piece.and_then(|p| p.ty) {
+                let color = piece.unwrap().color;
+                state.set_piece(sq as SquareIndex, Some(Piece { ty, color }));
+                state.revealed[sq] = color != view.perspective;
+            }
+        }
+        for (piece, ty) in self.hidden.iter().zip(types) {
+            let color = view.perspective.other();
+            state.set_piece(piece.square, Some(Piece { ty, color }));
+        }
+        state.set_turn(view.turn);
+        state.ply = view.history.len() as u32;
+        state.plies_since_capture = view.plies_since_capture;
+        state.captured = view.captured.clone();
+        Some(state)
+    }
+}
+
+/// Settings for [`ismcts`]. Fields left out when deserializing keep their
+/// defaults.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct IsmctsConfig {
+    /// The most deals to search. A timed search may stop sooner.
+    pub iterations: u32,
+    /// Playouts stop after this many plies and score the position with
+    /// `weights`.
+    pub max_playout_plies: u32,
+    pub weights: EvalWeights,
+    /// How strongly the tree favours rarely tried moves over good ones.
+    pub exploration: f64,
+    /// Seed for dealing hidden pieces and for playouts.
+    pub seed: u64,
+}
+
+impl Default for IsmctsConfig {
+    fn default() -> Self {
+        Self {
+            iterations: 2000,
+            max_playout_plies: 60,
+            weights: EvalWeights::default(),
+            exploration: 0.7,
+            seed: 0,
+        }
+    }
+}
+
+/// What an information set search found.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct IsmctsResult {
+    /// The most visited move, or `None` if nothing could be searched.
+    pub best_move: Option<Move>,
+    /// How often each move of the side to move was tried, most visited first.
+    pub visits: Vec<(Move, u32)>,
+    /// The number of iterations that found a deal agreeing with the view.
+    pub iterations: u32,
+}
+
+struct Node {
+    /// The move leading here, `None` at the root.
+    mv: Option<Move>,
+    /// The side that played `mv`.
+    mover: Color,
+    children: Vec<usize>,
+    parent: Option<usize>,
+    visits: u32,
+    /// How often this node's move was legal when its parent was visited.
+    availability: u32,
+    /// Sum of the playout scores for `mover`.
+    reward: f64,
+}
+
+impl Node {
+    fn new(mv: Option<Move>, mover: Color, parent: Option<usize>) -> Self {
+        Self {
+            mv,
+            mover,
+            children: Vec::new(),
+            parent,
+            visits: 0,
+            availability: 0,
+            reward: 0.0,
+        }
+    }
+}
+
+/// The score of `state` for Red, between 0 (Black wins) and 1 (Red wins).
+fn score_for_red(state: &GameState, weights: &EvalWeights) -> f64 {
+    let result = state.result();
+    if result.is_over() {
+        return match result.winner() {
+            Some(Color::Red) => 1.0,
+            Some(Color::Black) => 0.0,
+            None => 0.5,
+        };
+    }
+    let score = match state.turn {
+        Color::Red => weights.evaluate(state),
+        Color::Black => -weights.evaluate(state),
+    };
+    1.0 / (1.0 + (-score as f64 / 400.0).exp())
+}
+
+/// Searches for a move for the side to move using only what `view` shows.
+/// Only moves in `allowed` are tried at the root.
+pub fn ismcts(view: &PlayerView, allowed: &[Move], config: &IsmctsConfig) -> IsmctsResult {
+    run(view, allowed, config, || false)
+}
+
+/// Runs [`ismcts`], asking `should_stop` before every iteration after the
+/// first.
+fn run(
+    view: &PlayerView,
+    allowed: &[Move],
+    config: &IsmctsConfig,
+    mut should_stop: impl FnMut() -> bool,
+) -> IsmctsResult {
+    if allowed.is_empty() {
+        return IsmctsResult {
+            best_move: None,
+            visits: Vec::new(),
+            iterations: 0,
+        };
+    }
+    let determinizer = Determinizer::new(view);
+    let mut rng = seeded_rng(config.seed);
+    let mut tree = vec![Node::new(None, view.turn.other(), None)];
+    let mut iterations = 0;
+
+    for i in 0..config.iterations {
+        if i > 0 && should_stop() {
+            break;
+        }
+        let Some(mut state) = determinizer.sample(&mut rng) else {
+            continue;
+        };
+        iterations += 1;
+
+        // Selection and expansion, following only moves legal in this deal
+        let mut node = 0;
+        while !state.result().is_over() {
+            let mut moves = state.legal_moves();
+            if node == 0 {
+                moves.retain(|mv| allowed.contains(mv));
+            }
+            let mut untried = Vec::new();
+            for &mv in &moves {
+                match tree[node]
+                    .children
+                    .iter()
+                    .find(|&&c| tree[c].mv == Some(mv))
+                {
+                    Some(&child) => tree[child].availability += 1,
+                    None => untried.push(mv),
+                }
+            }
+            if !untried.is_empty() {
+                let mv = untried[rng.gen_range(0..untried.len())];
+                let child = tree.len();
+                tree.push(Node::new(Some(mv), state.turn, Some(node)));
+                tree[child].availability = 1;
+                tree[node].children.push(child);
+                state.make_move_unchecked(mv);
+                node = child;
+                break;
+            }
+            let parent = &tree[node];
+            let child = *parent
+                .children
+                .iter()
+                .filter(|&&c| moves.contains(&tree[c].mv.unwrap()))
+                .max_by(|&&a, &&b| {
+                    let ucb = |c: usize| {
+                        let n = &tree[c];
+                        n.reward / n.visits as f64
+                            + config.exploration
+                                * ((n.availability as f64).ln() / n.visits as f64).sqrt()
+                    };
+                    ucb(a).total_cmp(&ucb(b))
+                })
+                .expect("every legal move has a child");
+            state.make_move_unchecked(tree[child].mv.unwrap());
+            node = child;
+        }
+
+        // Playout
+        for _ in 0..config.max_playout_plies {
+            if state.result().is_over() {
+                break;
+            }
+            let moves = state.legal_moves();
+            state.make_move_unchecked(moves[rng.gen_range(0..moves.len())]);
+        }
+        let red = score_for_red(&state, &config.weights);
+
+        // Backpropagation
+        let mut current = Some(node);
+        while let Some(n) = current {
+            let node = &mut tree[n];
+            node.visits += 1;
+            node.reward += if node.mover == Color::Red {
+                red
+            } else {
+                1.0 - red
+            };
+            current = node.parent;
+        }
+    }
+
+    let mut visits: Vec<(Move, u32)> = tree[0]
+        .children
+        .iter()
+        .map(|&c| (tree[c].mv.unwrap(), tree[c].visits))
+        .collect();
+    visits.sort_by_key(|&(_, n)| std::cmp::Reverse(n));
+    IsmctsResult {
+        best_move: visits.first().map(|&(mv, _)| mv),
+        visits,
+        iterations,
+    }
+}
+
+impl Engine {
+    /// Searches the current game as `perspective` sees it, without looking
+    /// at the opponent's hidden pieces. See [`ismcts`]. A stop requested
+    /// through [`Engine::stop_flag`] ends the search after the current deal.
+    pub fn search_hidden(&self, perspective: Color, config: &IsmctsConfig) -> IsmctsResult {
+        self.search_hidden_with(perspective, config, &SearchLimits::default())
+    }
+
+    /// Like [`Engine::search_hidden`], but also stops at the soft time limit
+    /// of `limits`. `depth` and `nodes` do not
+    /// apply; the number of deals is capped by `config.iterations`.
+    pub fn search_hidden_with(
+        &self,
+        perspective: Color,
+        config: &IsmctsConfig,
+        limits: &SearchLimits,
+    ) -> IsmctsResult {
+        let allowed = self.state.clone().allowed_moves();
+        let budget = limits.time_budget();
+        let start_ms = if budget.is_some() {
+            self.clock.now_ms()
+        } else {
+            0
+        };
+        let result = run(&self.state.view(perspective), &allowed, config, || {
+            self.stop.is_stopped()
+                || budget
+                    .is_some_and(|(soft, _)| self.clock.now_ms().saturating_sub(start_ms) >= soft)
+        });
+        self.stop.reset();
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::limits::Clock;
+    use game::board::sq;
+    use game::rules::Rules;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    #[test]
+    fn test_deals_agree_with_view() {
+        let mut state = GameState::random_start(21);
+        for _ in 0..30 {
+            let moves = state.legal_moves();
+            state
+                .make_move(moves[state.ply as usize * 7 % moves.len()])
+                .unwrap();
+        }
+        let view = state.view(Color::Red);
+        let determinizer = Determinizer::new(&view);
+        let mut rng = PseudoRng::new(1);
+        for _ in 0..20 {
+            let sample = determinizer.sample(&mut rng).unwrap();
+            assert_eq!(sample.view(Color::Red).board, view.board);
+            for sq in view.hidden_squares() {
+                let piece = sample.board[sq as usize].unwrap();
+                let (origin, moved) = view.origin(sq);
+                assert!(can_start_on(&view.rules, piece, origin));
+                if moved {
+                    assert!(!matches!(piece.ty, PieceType::Landmine | PieceType::Flag));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_deals_from_the_start_are_legal() {
+        let state = GameState::random_start(8);
+        let determinizer = Determinizer::new(&state.view(Color::Black));
+        let mut rng = PseudoRng::new(2);
+        let sample = determinizer.sample(&mut rng).unwrap();
+        assert!(sample.is_startpos_legal());
+        assert_eq!(
+            sample.deployment(Color::Black),
+            state.deployment(Color::Black)
+        );
+    }
+
+    #[test]
+    fn test_finds_the_hidden_flag() {
+        // Black has only a flag and an army, so the piece in the headquarters
+        // must be the flag
+        let mut piece_counts = [0; NUM_PIECETYPES];
+        piece_counts[PieceType::Flag as usize] = 1;
+        piece_counts[PieceType::Army as usize] = 1;
+        let rules = Rules {
+            piece_counts,
+            ..Rules::default()
+        };
+        let mut state = GameState::with_rules(rules);
+        for (square, ty, color) in [
+            (sq::A2, PieceType::Flag, Color::Red),
+            (sq::L2, PieceType::Company, Color::Red),
+            (sq::M2, PieceType::Flag, Color::Black),
+            (sq::K5, PieceType::Army, Color::Black),
+        ] {
+            state.set_piece(square, Some(Piece { ty, color }));
+        }
+        let engine = Engine::with_state(state);
+        let config = IsmctsConfig {
+            iterations: 300,
+            ..IsmctsConfig::default()
+        };
+        let result = engine.search_hidden(Color::Red, &config);
+        assert_eq!(result.iterations, 300);
+        assert_eq!(result.best_move, Some(Move::new(sq::L2, sq::M2)));
+    }
+
+    #[test]
+    fn test_skips_banned_moves() {
+        // Black's army has stepped onto the square Red's field marshal just
+        // left. Taking it wins, but is Red's third move back and forth
+        let mut piece_counts = [0; NUM_PIECETYPES];
+        piece_counts[PieceType::Flag as usize] = 1;
+        piece_counts[PieceType::Army as usize] = 1;
+        let mut rules = Rules {
+            piece_counts,
+            ..Rules::default()
+        };
+        let take = Move::new(sq::J1, sq::J2);
+        let config = IsmctsConfig {
+            iterations: 300,
+            ..IsmctsConfig::default()
+        };
+        for max_shuttles in [5, 2] {
+            rules.repetition.max_shuttles = max_shuttles;
+            let mut state = GameState::with_rules(rules);
+            for (square, ty, color) in [
+                (sq::A2, PieceType::Flag, Color::Red),
+                (sq::J1, PieceType::Overall, Color::Red),
+                (sq::M2, PieceType::Flag, Color::Black),
+                (sq::K3, PieceType::Army, Color::Black),
+            ] {
+                state.set_piece(square, Some(Piece { ty, color }));
+            }
+            for (from, to) in [
+                (sq::J1, sq::J2),
+                (sq::K3, sq::K2),
+                (sq::J2, sq::J1),
+                (sq::K2, sq::J2),
+            ] {
+                state.make_move(Move::new(from, to)).unwrap();
+            }
+            let engine = Engine::with_state(state);
+            let best_move = engine.search_hidden(Color::Red, &config).best_move;
+            if max_shuttles == 5 {
+                assert_eq!(best_move, Some(take));
+            } else {
+                assert_ne!(best_move, Some(take));
+                assert!(engine
+                    .state()
+                    .clone()
+                    .allowed_moves()
+                    .contains(&best_move.unwrap()));
+            }
+        }
+    }
+
+    #[test]
+    fn test_search_from_random_start() {
+        let engine = Engine::with_state(GameState::random_start(4));
+        let config = IsmctsConfig {
+            iterations: 200,
+            max_playout_plies: 20,
+            ..IsmctsConfig::default()
+        };
+        let result = engine.search_hidden(Color::Red, &config);
+        assert!(engine
+            .state()
+            .legal_moves()
+            .contains(&result.best_move.unwrap()));
+        assert_eq!(result.visits.iter().map(|&(_, n)| n).sum::<u32>(), 200);
+    }
+
+    /// A clock that moves on a millisecond every time it is read.
+    struct TickingClock(AtomicU64);
+
+    impl Clock for TickingClock {
+        fn now_ms(&self) -> u64 {
+            self.0.fetch_add(1, Ordering::Relaxed)
+        }
+    }
+
+    #[test]
+    fn test_move_time() {
+        let mut engine = Engine::with_state(GameState::random_start(4));
+        engine.set_clock(Box::new(TickingClock(AtomicU64::new(0))));
+        let config = IsmctsConfig {
+            iterations: u32::MAX,
+            max_playout_plies: 20,
+            ..IsmctsConfig::default()
+        };
+        let result = engine.search_hidden_with(Color::Red, &config, &SearchLimits::move_time(20));
+        assert!(result.best_move.is_some());
+        // The clock is read once at the start and once before each deal
+        assert!(result.iterations <= 20);
+    }
+
+    #[test]
+    fn test_stop_before_search() {
+        let engine = Engine::with_state(GameState::random_start(4));
+        engine.stop_flag().stop();
+        let result = engine.search_hidden(Color::Red, &IsmctsConfig::default());
+        assert_eq!(result.iterations, 1);
+        assert!(result.best_move.is_some());
+        assert!(!engine.stop_flag().is_stopped());
+    }
+}
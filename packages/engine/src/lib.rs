@@ -1,17 +1,60 @@
+pub mod belief;
+pub mod evaluation;
+pub mod ismcts;
+pub mod limits;
+pub mod search;
+pub mod tt;
+
+use evaluation::EvalWeights;
 use game::board::GameState;
+use limits::{Clock, StopFlag, SystemClock};
+use tt::TranspositionTable;
 
 pub struct Engine {
     state: GameState,
+    weights: EvalWeights,
+    tt: TranspositionTable,
+    stop: StopFlag,
+    clock: Box<dyn Clock>,
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Engine {
     pub fn new() -> Self {
+        Self::with_state(GameState::new())
+    }
+
+    /// An engine for an existing game.
+    pub fn with_state(state: GameState) -> Self {
         Self {
-            state: GameState::new(),
+            state,
+            weights: EvalWeights::default(),
+            tt: TranspositionTable::default(),
+            stop: StopFlag::default(),
+            clock: Box::new(SystemClock),
         }
     }
 
     pub fn state(&self) -> &GameState {
         &self.state
     }
+
+    /// The game, for playing moves on it between searches.
+    pub fn state_mut(&mut self) -> &mut GameState {
+        &mut self.state
+    }
+
+    pub fn weights(&self) -> &EvalWeights {
+        &self.weights
+    }
+
+    /// Replaces the evaluation weights used by [`Engine::search`].
+    pub fn set_weights(&mut self, weights: EvalWeights) {
+        self.weights = weights;
+    }
 }
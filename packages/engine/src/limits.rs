@@ -0,0 +1,181 @@
+//! Search limits and time management.
+//!
+//! A search can be limited by depth, by the number of positions visited, by
+//! a fixed time for the move, or by the time left on the player's clock plus
+//! the increment added after each move. From the clock the search gets a
+//! soft limit, after which it starts no new iteration, and a hard limit, at
+//! which it abandons the iteration it is in. Another thread can end the
+//! search early through a [`StopFlag`].
+//!
+//! Whatever the limits, the first iteration is always completed so that
+//! there is a move to play. The hidden-piece search reads the same limits
+//! through [`Engine::search_hidden_with`], checking them between deals.
+//!
+//! Time is read from a [`Clock`]. [`SystemClock`] uses [`std::time::Instant`],
+//! which is not available on `wasm32-unknown-unknown`, so the WASM build
+//! gives the engine its own clock with [`Engine::set_clock`].
+
+use crate::Engine;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::Instant;
+
+/// The deepest a search goes when no depth is given.
+pub const MAX_DEPTH: u32 = 64;
+
+/// How many more moves the time left is shared between.
+const MOVES_TO_GO: u64 = 30;
+
+/// Time kept back from every move for the moves to reach the server.
+const MOVE_OVERHEAD_MS: u64 = 20;
+
+/// When a search should stop. Limits left as `None` do not apply, so the
+/// default searches to [`MAX_DEPTH`] unless stopped.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct SearchLimits {
+    /// The deepest iteration to search, in plies.
+    pub depth: Option<u32>,
+    /// The number of positions to visit.
+    pub nodes: Option<u64>,
+    /// The time to spend on this move.
+    pub move_time_ms: Option<u64>,
+    /// The time left on the clock of the side to move.
+    pub time_left_ms: Option<u64>,
+    /// The time added to the clock after each move.
+    pub increment_ms: u64,
+}
+
+impl SearchLimits {
+    pub fn depth(depth: u32) -> Self {
+        Self {
+            depth: Some(depth),
+            ..Self::default()
+        }
+    }
+
+    pub fn nodes(nodes: u64) -> Self {
+        Self {
+            nodes: Some(nodes),
+            ..Self::default()
+        }
+    }
+
+    pub fn move_time(ms: u64) -> Self {
+        Self {
+            move_time_ms: Some(ms),
+            ..Self::default()
+        }
+    }
+
+    /// Lets the search share out the time left on the clock.
+    pub fn clock(time_left_ms: u64, increment_ms: u64) -> Self {
+        Self {
+            time_left_ms: Some(time_left_ms),
+            increment_ms,
+            ..Self::default()
+        }
+    }
+
+    /// The soft and hard time limits, in milliseconds from the start of the
+    /// search, or `None` if the search is not timed.
+    pub fn time_budget(&self) -> Option<(u64, u64)> {
+        let from_clock = self.time_left_ms.map(|left| {
+            let available = left.saturating_sub(MOVE_OVERHEAD_MS);
+            let hard = available / 2;
+            let soft = (available / MOVES_TO_GO + self.increment_ms * 3 / 4).min(hard);
+            (soft, (soft * 4).min(hard))
+        });
+        match (self.move_time_ms, from_clock) {
+            (Some(ms), Some((soft, hard))) => Some((ms.min(soft), ms.min(hard))),
+            (Some(ms), None) => Some((ms, ms)),
+            (None, budget) => budget,
+        }
+    }
+}
+
+/// Tells a running search to stop. Clones share the same flag, so one can be
+/// handed to another thread.
+#[derive(Debug, Clone, Default)]
+pub struct StopFlag(Arc<AtomicBool>);
+
+impl StopFlag {
+    pub fn stop(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn reset(&self) {
+        self.0.store(false, Ordering::Relaxed);
+    }
+}
+
+/// A source of time for timed searches.
+pub trait Clock: Send {
+    /// Milliseconds since some fixed point.
+    fn now_ms(&self) -> u64;
+}
+
+/// The standard library clock. It is only read by timed searches.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_ms(&self) -> u64 {
+        static START: OnceLock<Instant> = OnceLock::new();
+        START.get_or_init(Instant::now).elapsed().as_millis() as u64
+    }
+}
+
+impl Engine {
+    /// A handle that stops the current search, or the next one if none is
+    /// running. The flag is cleared when the search it stopped returns.
+    pub fn stop_flag(&self) -> StopFlag {
+        self.stop.clone()
+    }
+
+    /// Replaces the clock timed searches read.
+    pub fn set_clock(&mut self, clock: Box<dyn Clock>) {
+        self.clock = clock;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_time_budget() {
+        assert_eq!(SearchLimits::depth(4).time_budget(), None);
+        assert_eq!(SearchLimits::move_time(500).time_budget(), Some((500, 500)));
+
+        // A thirtieth of the time left, plus most of the increment
+        let (soft, hard) = SearchLimits::clock(60_020, 1000).time_budget().unwrap();
+        assert_eq!(soft, 2000 + 750);
+        assert_eq!(hard, 4 * soft);
+
+        // Never more than half of what is left
+        let (soft, hard) = SearchLimits::clock(1020, 5000).time_budget().unwrap();
+        assert_eq!((soft, hard), (500, 500));
+
+        let limits = SearchLimits {
+            move_time_ms: Some(1000),
+            ..SearchLimits::clock(60_020, 0)
+        };
+        assert_eq!(limits.time_budget(), Some((1000, 1000)));
+    }
+
+    #[test]
+    fn test_stop_flag_is_shared() {
+        let flag = StopFlag::default();
+        let other = flag.clone();
+        other.stop();
+        assert!(flag.is_stopped());
+        flag.reset();
+        assert!(!other.is_stopped());
+    }
+}
@@ -0,0 +1,466 @@
+//! Iterative-deepening alpha-beta search.
+//!
+//! The search sees the whole board, as if every piece had been revealed.
+//! Each iteration searches one ply deeper than the last with a negamax
+//! alpha-beta search, trying the previous iteration's principal variation
+//! first, then attacks on the most valuable pieces, then quiet moves. Moves
+//! are played and taken back on a single copy of the game with
+//! [`GameState::make_move_unchecked`] and [`GameState::unmake_move`].
+//!
+//! Repetition limits are applied at the root only; checking them deeper in
+//! the tree would replay the game history at every node. For the same reason
+//! the root never takes its score from the [`TranspositionTable`], though it
+//! does try the stored best move first.
+//!
+//! How long to search is set by [`SearchLimits`]. An iteration cut short by
+//! a limit is thrown away and the previous one is used.
+//!
+//! Scores of won games count plies from the root, so they are stored in the
+//! table counting from the position instead, and converted back on probing.
+
+use crate::evaluation::EvalWeights;
+use crate::limits::{Clock, SearchLimits, StopFlag, MAX_DEPTH};
+use crate::tt::{Bound, TranspositionTable};
+use crate::Engine;
+use game::board::GameState;
+use game::movegen::Move;
+use serde::{Deserialize, Serialize};
+
+/// The score of a won game, less the number of plies it takes to win.
+pub const WIN_SCORE: i32 = 1_000_000;
+const INFINITY: i32 = WIN_SCORE + 1;
+
+/// What a search found.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SearchResult {
+    /// The move to play, or `None` if the game is already over.
+    pub best_move: Option<Move>,
+    /// The score for the side to move, in [`EvalWeights`] units. Scores
+    /// within 1000 of [`WIN_SCORE`] are forced wins or losses.
+    pub score: i32,
+    /// The expected line of play, starting with `best_move`.
+    pub pv: Vec<Move>,
+    /// The depth of the last completed iteration, in plies.
+    pub depth: u32,
+    /// The number of positions visited, including by an iteration that was
+    /// cut short.
+    pub nodes: u64,
+}
+
+/// How often the searcher looks at the clock and the stop flag, in nodes.
+const CHECK_INTERVAL: u64 = 1024;
+
+/// Converts a score counted from the root to one counted from the position
+/// `ply` plies in.
+fn score_to_tt(score: i32, ply: usize) -> i32 {
+    if score > WIN_SCORE - 1000 {
+        score + ply as i32
+    } else if score < -(WIN_SCORE - 1000) {
+        score - ply as i32
+    } else {
+        score
+    }
+}
+
+fn score_from_tt(score: i32, ply: usize) -> i32 {
+    if score > WIN_SCORE - 1000 {
+        score - ply as i32
+    } else if score < -(WIN_SCORE - 1000) {
+        score + ply as i32
+    } else {
+        score
+    }
+}
+
+struct Searcher<'a> {
+    state: GameState,
+    tt: &'a mut TranspositionTable,
+    weights: EvalWeights,
+    nodes: u64,
+    /// The principal variation of the previous iteration.
+    prev_pv: Vec<Move>,
+    clock: &'a dyn Clock,
+    stop: StopFlag,
+    start_ms: u64,
+    hard_limit_ms: Option<u64>,
+    max_nodes: Option<u64>,
+    /// Whether the limits apply yet; they do not in the first iteration.
+    limited: bool,
+    /// Set once a limit is reached, after which every node returns at once.
+    aborted: bool,
+}
+
+impl Searcher<'_> {
+    /// Orders `moves` so that the previous principal variation comes first,
+    /// then the stored best move, then attacks, most valuable target first.
+    fn order_moves(&self, moves: &mut [Move], ply: usize, tt_move: Option<Move>) {
+        let pv_move = self.prev_pv.get(ply).copied();
+        moves.sort_by_cached_key(|&mv| {
+            if Some(mv) == pv_move {
+                return i32::MIN;
+            }
+            if Some(mv) == tt_move {
+                return i32::MIN + 1;
+            }
+            match self.state.board[mv.to as usize] {
+                Some(target) => {
+                    let attacker = self.state.board[mv.from as usize].unwrap();
+                    -(16 * self.weights.piece_value(target.ty)
+                        - self.weights.piece_value(attacker.ty))
+                }
+                None => 0,
+            }
+        });
+    }
+
+    fn elapsed_ms(&self) -> u64 {
+        self.clock.now_ms().saturating_sub(self.start_ms)
+    }
+
+    /// Whether a limit has been reached in the middle of an iteration.
+    fn out_of_budget(&self) -> bool {
+        if self.max_nodes.is_some_and(|max| self.nodes >= max) {
+            return true;
+        }
+        if !self.nodes.is_multiple_of(CHECK_INTERVAL) {
+            return false;
+        }
+        self.stop.is_stopped()
+            || self
+                .hard_limit_ms
+                .is_some_and(|limit| self.elapsed_ms() >= limit)
+    }
+
+    fn negamax(
+        &mut self,
+        depth: u32,
+        ply: usize,
+        mut alpha: i32,
+        beta: i32,
+        pv: &mut Vec<Move>,
+    ) -> i32 {
+        if self.aborted || (self.limited && self.out_of_budget()) {
+            self.aborted = true;
+            return 0;
+        }
+        self.nodes += 1;
+        pv.clear();
+
+        let result = self.state.result();
+        if result.is_over() {
+            return match result.winner() {
+                Some(color) if color == self.state.turn => WIN_SCORE - ply as i32,
+                Some(_) => -(WIN_SCORE - ply as i32),
+                None => 0,
+            };
+        }
+        if depth == 0 {
+            return self.weights.evaluate(&self.state);
+        }
+
+        let key = self.state.zobrist_key();
+        let entry = self.tt.probe(key);
+        if let Some(entry) = entry.filter(|e| ply > 0 && e.depth >= depth) {
+            let score = score_from_tt(entry.score, ply);
+            let cutoff = match entry.bound {
+                Bound::Exact => true,
+                Bound::Lower => score >= beta,
+                Bound::Upper => score <= alpha,
+            };
+            if cutoff {
+                pv.extend(entry.best_move);
+                return score;
+            }
+        }
+
+        // The game is lost rather than over the limits if there is no
+        // allowed move, so the root always has one
+        let mut moves = if ply == 0 {
+            self.state.allowed_moves()
+        } else {
+            self.state.legal_moves()
+        };
+        self.order_moves(&mut moves, ply, entry.and_then(|e| e.best_move));
+
+        let original_alpha = alpha;
+        let mut best = -INFINITY;
+        let mut best_move = None;
+        let mut line = Vec::new();
+        for mv in moves {
+            self.state.make_move_unchecked(mv);
+            let score = -self.negamax(depth - 1, ply + 1, -beta, -alpha, &mut line);
+            self.state.unmake_move();
+            if self.aborted {
+                return 0;
+            }
+
+            if score > best {
+                best = score;
+                best_move = Some(mv);
+                if score > alpha {
+                    alpha = score;
+                    pv.clear();
+                    pv.push(mv);
+                    pv.extend_from_slice(&line);
+                }
+            }
+            if alpha >= beta {
+                break;
+            }
+        }
+
+        let bound = if best <= original_alpha {
+            Bound::Upper
+        } else if best >= beta {
+            Bound::Lower
+        } else {
+            Bound::Exact
+        };
+        self.tt
+            .store(key, depth, score_to_tt(best, ply), bound, best_move);
+        best
+    }
+}
+
+impl Engine {
+    /// Searches the current position to `depth` plies, one iteration at a
+    /// time, and returns the result of the deepest iteration. The
+    /// transposition table is kept for the next search.
+    pub fn search(&mut self, depth: u32) -> SearchResult {
+        self.search_with(&SearchLimits::depth(depth))
+    }
+
+    /// Searches the current position one iteration at a time until a limit
+    /// is reached or the search is stopped, and returns the result of the
+    /// deepest completed iteration. A stop requested before the search
+    /// starts ends it after the first iteration.
+    pub fn search_with(&mut self, limits: &SearchLimits) -> SearchResult {
+        self.tt.new_search();
+        let budget = limits.time_budget();
+        let mut searcher = Searcher {
+            state: self.state.clone(),
+            tt: &mut self.tt,
+            weights: self.weights,
+            nodes: 0,
+            prev_pv: Vec::new(),
+            clock: self.clock.as_ref(),
+            stop: self.stop.clone(),
+            start_ms: if budget.is_some() {
+                self.clock.now_ms()
+            } else {
+                0
+            },
+            hard_limit_ms: budget.map(|(_, hard)| hard),
+            max_nodes: limits.nodes,
+            limited: false,
+            aborted: false,
+        };
+        let mut result = SearchResult {
+            best_move: None,
+            score: 0,
+            pv: Vec::new(),
+            depth: 0,
+            nodes: 0,
+        };
+        for d in 1..=limits.depth.unwrap_or(MAX_DEPTH).max(1) {
+            let mut pv = Vec::new();
+            let score = searcher.negamax(d, 0, -INFINITY, INFINITY, &mut pv);
+            if searcher.aborted {
+                break;
+            }
+            result = SearchResult {
+                best_move: pv.first().copied(),
+                score,
+                pv: pv.clone(),
+                depth: d,
+                nodes: searcher.nodes,
+            };
+            if pv.is_empty() || score.abs() > WIN_SCORE - 1000 {
+                break;
+            }
+            searcher.prev_pv = pv;
+            searcher.limited = true;
+
+            let out_of_time = budget.is_some_and(|(soft, _)| searcher.elapsed_ms() >= soft);
+            let out_of_nodes = limits.nodes.is_some_and(|max| searcher.nodes >= max);
+            if out_of_time || out_of_nodes || searcher.stop.is_stopped() {
+                break;
+            }
+        }
+        result.nodes = searcher.nodes;
+        self.stop.reset();
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use game::board::{sq, Color, Piece, PieceType, SquareIndex};
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::thread;
+    use std::time::Duration;
+
+    fn engine_with(pieces: &[(SquareIndex, PieceType, Color)]) -> Engine {
+        let mut state = GameState::new();
+        for &(square, ty, color) in pieces {
+            state.set_piece(square, Some(Piece { ty, color }));
+        }
+        Engine::with_state(state)
+    }
+
+    #[test]
+    fn test_captures_the_flag() {
+        let mut engine = engine_with(&[
+            (sq::A2, PieceType::Flag, Color::Red),
+            (sq::B3, PieceType::Platoon, Color::Red),
+            (sq::M2, PieceType::Flag, Color::Black),
+            (sq::L2, PieceType::Company, Color::Red),
+            (sq::K5, PieceType::Army, Color::Black),
+        ]);
+        let result = engine.search(3);
+        assert_eq!(result.best_move, Some(Move::new(sq::L2, sq::M2)));
+        assert_eq!(result.score, WIN_SCORE - 1);
+        assert_eq!(result.pv, vec![Move::new(sq::L2, sq::M2)]);
+    }
+
+    #[test]
+    fn test_wins_material_and_keeps_it() {
+        // The Red army in the camp on E2 can take the division on E3; the
+        // Black army on K3 is too far away to take it back
+        let mut engine = engine_with(&[
+            (sq::A2, PieceType::Flag, Color::Red),
+            (sq::E2, PieceType::Army, Color::Red),
+            (sq::M2, PieceType::Flag, Color::Black),
+            (sq::E3, PieceType::Division, Color::Black),
+            (sq::K3, PieceType::Army, Color::Black),
+        ]);
+        // Material only, so that the score is easy to check
+        let material = EvalWeights {
+            flag_guard: 0,
+            flag_threat: 0,
+            frontline: 0,
+            camp: 0,
+            engineer_mobility: 0,
+            ..EvalWeights::default()
+        };
+        engine.set_weights(material);
+        let result = engine.search(3);
+        assert_eq!(result.best_move, Some(Move::new(sq::E2, sq::E3)));
+        assert_eq!(result.depth, 3);
+        assert_eq!(result.pv.len(), 3);
+        // Level on material after starting a division down
+        assert_eq!(
+            material.evaluate(engine.state()),
+            -material.piece_value(PieceType::Division)
+        );
+        assert_eq!(result.score, 0);
+    }
+
+    #[test]
+    fn test_transposition_table_carries_over() {
+        let mut engine = Engine::with_state(GameState::random_start(8));
+        let first = engine.search(3);
+        let second = engine.search(3);
+        assert_eq!(second.best_move, first.best_move);
+        assert_eq!(second.score, first.score);
+        assert!(second.nodes < first.nodes);
+
+        engine.clear_tt();
+        assert_eq!(engine.search(3).nodes, first.nodes);
+    }
+
+    #[test]
+    fn test_no_move_over_the_repetition_limits() {
+        let mut engine = engine_with(&[
+            (sq::A2, PieceType::Flag, Color::Red),
+            (sq::B1, PieceType::Army, Color::Red),
+            (sq::C1, PieceType::Landmine, Color::Red),
+            (sq::B2, PieceType::Landmine, Color::Red),
+            (sq::M2, PieceType::Flag, Color::Black),
+            (sq::L5, PieceType::Platoon, Color::Black),
+        ]);
+        let state = engine.state_mut();
+        state.rules.repetition.max_shuttles = 3;
+        for (from, to) in [
+            (sq::B1, sq::A1),
+            (sq::L5, sq::K5),
+            (sq::A1, sq::B1),
+            (sq::K5, sq::J5),
+            (sq::B1, sq::A1),
+            (sq::J5, sq::I5),
+        ] {
+            state.make_move(Move::new(from, to)).unwrap();
+        }
+        // Going back to B1 is the army's only move, and it is one too many
+        let result = engine.search(2);
+        assert_eq!(result.best_move, None);
+        assert_eq!(result.score, -WIN_SCORE);
+    }
+
+    #[test]
+    fn test_search_from_random_start() {
+        let mut engine = Engine::with_state(GameState::random_start(3));
+        let result = engine.search(2);
+        let best = result.best_move.unwrap();
+        assert!(engine.state_mut().allowed_moves().contains(&best));
+        assert!(result.nodes > engine.state().legal_moves().len() as u64);
+    }
+
+    #[test]
+    fn test_node_limit() {
+        let mut engine = Engine::with_state(GameState::random_start(3));
+        let first = engine.search(1);
+        let result = engine.search_with(&SearchLimits::nodes(first.nodes + 500));
+        assert!(result.best_move.is_some());
+        assert!(result.nodes <= first.nodes + 500);
+        assert!(result.depth >= 1);
+    }
+
+    /// A clock that moves on a millisecond every time it is read.
+    struct TickingClock(AtomicU64);
+
+    impl Clock for TickingClock {
+        fn now_ms(&self) -> u64 {
+            self.0.fetch_add(1, Ordering::Relaxed)
+        }
+    }
+
+    #[test]
+    fn test_move_time() {
+        let mut engine = Engine::with_state(GameState::random_start(3));
+        engine.set_clock(Box::new(TickingClock(AtomicU64::new(0))));
+        let result = engine.search_with(&SearchLimits::move_time(20));
+        assert!(result.best_move.is_some());
+        // Checked about every thousand nodes, so twenty reads is not deep
+        assert!(result.depth < 6);
+        assert!(result.nodes <= (20 + 2) * CHECK_INTERVAL);
+    }
+
+    #[test]
+    fn test_stop_from_another_thread() {
+        let mut engine = Engine::with_state(GameState::random_start(3));
+        let stop = engine.stop_flag();
+        let handle = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            stop.stop();
+        });
+        let result = engine.search_with(&SearchLimits::default());
+        handle.join().unwrap();
+        assert!(result.best_move.is_some());
+        assert!(result.depth < MAX_DEPTH);
+    }
+
+    #[test]
+    fn test_stop_before_search() {
+        let mut engine = Engine::with_state(GameState::random_start(3));
+        engine.stop_flag().stop();
+        let result = engine.search(4);
+        assert_eq!(result.depth, 1);
+        assert!(result.best_move.is_some());
+
+        // The flag is cleared for the next search
+        assert!(!engine.stop_flag().is_stopped());
+        assert_eq!(engine.search(2).depth, 2);
+    }
+}
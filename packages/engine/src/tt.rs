@@ -0,0 +1,174 @@
+//! Transposition table.
+//!
+//! Railroad moves reach the same position by many different orders of
+//! moves, so [`Engine::search`] remembers what it found about each position
+//! it searched, keyed by [`GameState::zobrist_key`]. The table has a fixed
+//! number of slots, one entry each, chosen by the key. A new entry replaces
+//! the one in its slot if it was searched at least as deep, or if the old
+//! entry is left over from an earlier search.
+//!
+//! [`GameState::zobrist_key`]: game::board::GameState::zobrist_key
+
+use crate::Engine;
+use game::movegen::Move;
+use std::mem::size_of;
+
+/// The table size an [`Engine`] starts with, in megabytes.
+pub const DEFAULT_TT_SIZE_MB: usize = 16;
+
+/// How a stored score relates to the true score of the position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bound {
+    /// The score is exact.
+    Exact,
+    /// The true score is at least the stored one: a move failed high.
+    Lower,
+    /// The true score is at most the stored one: every move failed low.
+    Upper,
+}
+
+/// What a search found about one position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Entry {
+    pub key: u64,
+    /// The depth the position was searched to, in plies.
+    pub depth: u32,
+    pub score: i32,
+    pub bound: Bound,
+    /// The best move found, if any move was searched.
+    pub best_move: Option<Move>,
+    /// The search that stored the entry.
+    age: u8,
+}
+
+/// A fixed-size table of search results.
+pub struct TranspositionTable {
+    slots: Vec<Option<Entry>>,
+    age: u8,
+}
+
+impl Default for TranspositionTable {
+    fn default() -> Self {
+        Self::new(DEFAULT_TT_SIZE_MB)
+    }
+}
+
+impl TranspositionTable {
+    /// A table taking about `size_mb` megabytes, with at least one slot.
+    pub fn new(size_mb: usize) -> Self {
+        let len = (size_mb * 1024 * 1024 / size_of::<Option<Entry>>()).max(1);
+        Self {
+            slots: vec![None; len],
+            age: 0,
+        }
+    }
+
+    /// The number of entries the table can hold.
+    pub fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    fn index(&self, key: u64) -> usize {
+        (key % self.slots.len() as u64) as usize
+    }
+
+    /// The entry for the position with `key`, if the table still has one.
+    pub fn probe(&self, key: u64) -> Option<Entry> {
+        self.slots[self.index(key)].filter(|e| e.key == key)
+    }
+
+    /// Stores what a search found about the position with `key`, unless its
+    /// slot holds a deeper entry from the current search.
+    pub fn store(
+        &mut self,
+        key: u64,
+        depth: u32,
+        score: i32,
+        bound: Bound,
+        best_move: Option<Move>,
+    ) {
+        let age = self.age;
+        let i = self.index(key);
+        let slot = &mut self.slots[i];
+        if let Some(old) = slot {
+            if old.age == age && old.depth > depth {
+                return;
+            }
+        }
+        *slot = Some(Entry {
+            key,
+            depth,
+            score,
+            bound,
+            best_move,
+            age,
+        });
+    }
+
+    /// Marks every entry as coming from an earlier search, so that new
+    /// entries replace them regardless of depth. Entries can still be
+    /// probed until they are replaced.
+    pub fn new_search(&mut self) {
+        self.age = self.age.wrapping_add(1);
+    }
+
+    pub fn clear(&mut self) {
+        self.slots.fill(None);
+    }
+}
+
+impl Engine {
+    /// Replaces the transposition table with an empty one of about
+    /// `size_mb` megabytes.
+    pub fn set_tt_size(&mut self, size_mb: usize) {
+        self.tt = TranspositionTable::new(size_mb);
+    }
+
+    /// Forgets everything earlier searches found.
+    pub fn clear_tt(&mut self) {
+        self.tt.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use game::board::sq;
+
+    #[test]
+    fn test_size_in_megabytes() {
+        let tt = TranspositionTable::new(4);
+        assert_eq!(tt.capacity(), (4 << 20) / size_of::<Option<Entry>>());
+        assert_eq!(TranspositionTable::new(0).capacity(), 1);
+    }
+
+    #[test]
+    fn test_store_and_probe() {
+        let mut tt = TranspositionTable::new(1);
+        let mv = Some(Move::new(sq::B1, sq::C1));
+        tt.store(42, 3, -15, Bound::Lower, mv);
+        let entry = tt.probe(42).unwrap();
+        assert_eq!((entry.depth, entry.score), (3, -15));
+        assert_eq!((entry.bound, entry.best_move), (Bound::Lower, mv));
+        // Another key in the same slot
+        assert_eq!(tt.probe(42 + tt.capacity() as u64), None);
+        tt.clear();
+        assert_eq!(tt.probe(42), None);
+    }
+
+    #[test]
+    fn test_replace_by_depth() {
+        let mut tt = TranspositionTable::new(0);
+        tt.store(1, 5, 100, Bound::Exact, None);
+        tt.store(2, 4, 200, Bound::Exact, None);
+        assert_eq!(tt.probe(1).unwrap().score, 100);
+        tt.store(2, 5, 200, Bound::Exact, None);
+        assert_eq!(tt.probe(2).unwrap().score, 200);
+
+        // Anything replaces entries from an earlier search
+        tt.new_search();
+        assert!(tt.probe(2).is_some());
+        tt.store(3, 1, 300, Bound::Upper, None);
+        assert_eq!(tt.probe(3).unwrap().score, 300);
+    }
+}
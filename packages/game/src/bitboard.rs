@@ -0,0 +1,275 @@
+//! Bitboards: sets of squares packed into a `u128`, bit `i` standing for
+//! square `i`.
+//!
+//! Masks for each kind of square are derived from [`SQUARE_TO_SQUARETYPE`].
+//! [`GameState`] also keeps an [`Occupancy`] of the board, with one bitboard
+//! per color and per piece type, so that move generation and the game result
+//! can find pieces without scanning `board`. Like the Zobrist key it is
+//! updated as moves are made and rebuilt by [`GameState::refresh`].
+
+use crate::board::{
+    Color, GameState, Piece, PieceType, SquareIndex, SquareType, NUM_PIECETYPES, NUM_SQUARES,
+    SQUARE_TO_SQUARETYPE,
+};
+use std::ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, BitXorAssign, Not};
+
+/// A set of squares.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Bitboard(pub u128);
+
+impl Bitboard {
+    pub const EMPTY: Bitboard = Bitboard(0);
+    pub const ALL: Bitboard = Bitboard((1 << NUM_SQUARES) - 1);
+
+    /// The set holding only `sq`.
+    pub const fn square(sq: SquareIndex) -> Self {
+        Bitboard(1 << sq)
+    }
+
+    /// Every square of type `ty`.
+    pub const fn of_type(ty: SquareType) -> Self {
+        let mut bits = 0;
+        let mut i = 0;
+        while i < NUM_SQUARES {
+            if SQUARE_TO_SQUARETYPE[i] as u8 == ty as u8 {
+                bits |= 1 << i;
+            }
+            i += 1;
+        }
+        Bitboard(bits)
+    }
+
+    pub const fn contains(self, sq: SquareIndex) -> bool {
+        (sq as usize) < NUM_SQUARES && self.0 & (1 << sq) != 0
+    }
+
+    pub fn insert(&mut self, sq: SquareIndex) {
+        self.0 |= 1 << sq;
+    }
+
+    pub fn remove(&mut self, sq: SquareIndex) {
+        self.0 &= !(1 << sq);
+    }
+
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// The number of squares in the set.
+    pub const fn count(self) -> u32 {
+        self.0.count_ones()
+    }
+
+    /// The lowest square in the set.
+    pub const fn first(self) -> Option<SquareIndex> {
+        if self.0 == 0 {
+            None
+        } else {
+            Some(self.0.trailing_zeros() as SquareIndex)
+        }
+    }
+
+    /// The squares in the set, in index order.
+    pub fn squares(self) -> Squares {
+        Squares(self.0)
+    }
+}
+
+/// Iterator over the squares of a [`Bitboard`], lowest first.
+#[derive(Debug, Clone)]
+pub struct Squares(u128);
+
+impl Iterator for Squares {
+    type Item = SquareIndex;
+
+    fn next(&mut self) -> Option<SquareIndex> {
+        let sq = Bitboard(self.0).first()?;
+        self.0 &= self.0 - 1;
+        Some(sq)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let n = self.0.count_ones() as usize;
+        (n, Some(n))
+    }
+}
+
+impl ExactSizeIterator for Squares {}
+
+impl IntoIterator for Bitboard {
+    type Item = SquareIndex;
+    type IntoIter = Squares;
+
+    fn into_iter(self) -> Squares {
+        self.squares()
+    }
+}
+
+impl FromIterator<SquareIndex> for Bitboard {
+    fn from_iter<I: IntoIterator<Item = SquareIndex>>(iter: I) -> Self {
+        let mut bb = Bitboard::EMPTY;
+        for sq in iter {
+            bb.insert(sq);
+        }
+        bb
+    }
+}
+
+impl BitAnd for Bitboard {
+    type Output = Bitboard;
+    fn bitand(self, rhs: Bitboard) -> Bitboard {
+        Bitboard(self.0 & rhs.0)
+    }
+}
+
+impl BitOr for Bitboard {
+    type Output = Bitboard;
+    fn bitor(self, rhs: Bitboard) -> Bitboard {
+        Bitboard(self.0 | rhs.0)
+    }
+}
+
+impl BitXor for Bitboard {
+    type Output = Bitboard;
+    fn bitxor(self, rhs: Bitboard) -> Bitboard {
+        Bitboard(self.0 ^ rhs.0)
+    }
+}
+
+/// The complement within the board; bits past the last square stay clear.
+impl Not for Bitboard {
+    type Output = Bitboard;
+    fn not(self) -> Bitboard {
+        Bitboard(!self.0 & Bitboard::ALL.0)
+    }
+}
+
+impl BitAndAssign for Bitboard {
+    fn bitand_assign(&mut self, rhs: Bitboard) {
+        self.0 &= rhs.0;
+    }
+}
+
+impl BitOrAssign for Bitboard {
+    fn bitor_assign(&mut self, rhs: Bitboard) {
+        self.0 |= rhs.0;
+    }
+}
+
+impl BitXorAssign for Bitboard {
+    fn bitxor_assign(&mut self, rhs: Bitboard) {
+        self.0 ^= rhs.0;
+    }
+}
+
+/// Railroad squares, not including the frontline.
+pub const RAILROADS: Bitboard = Bitboard::of_type(SquareType::Railroad);
+pub const CAMPS: Bitboard = Bitboard::of_type(SquareType::Camp);
+pub const HQS: Bitboard = Bitboard::of_type(SquareType::HQ);
+pub const FRONTLINE: Bitboard = Bitboard::of_type(SquareType::Frontline);
+pub const MOUNTAINS: Bitboard = Bitboard::of_type(SquareType::Mountain);
+/// The railroad network: railroads and the frontline crossings.
+pub const RAIL_NETWORK: Bitboard = Bitboard(RAILROADS.0 | FRONTLINE.0);
+/// Squares a piece may end a move on.
+pub const LANDABLE: Bitboard = Bitboard(Bitboard::ALL.0 & !(FRONTLINE.0 | MOUNTAINS.0));
+
+/// Where the pieces are, by color and by piece type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Occupancy {
+    by_color: [Bitboard; 2],
+    by_type: [Bitboard; NUM_PIECETYPES],
+}
+
+impl Occupancy {
+    /// Builds the occupancy of `board` from scratch.
+    pub fn from_board(board: &[Option<Piece>]) -> Self {
+        let mut occupancy = Occupancy::default();
+        for (sq, piece) in board.iter().enumerate() {
+            occupancy.toggle(sq as SquareIndex, *piece);
+        }
+        occupancy
+    }
+
+    /// Adds `piece` on `sq` if it is not there, or removes it if it is.
+    /// Does nothing for an empty square.
+    pub(crate) fn toggle(&mut self, sq: SquareIndex, piece: Option<Piece>) {
+        if let Some(p) = piece {
+            let bit = Bitboard::square(sq);
+            self.by_color[p.color as usize] ^= bit;
+            self.by_type[p.ty as usize] ^= bit;
+        }
+    }
+
+    /// Every occupied square.
+    pub fn occupied(&self) -> Bitboard {
+        self.by_color[0] | self.by_color[1]
+    }
+
+    /// The squares holding `color`'s pieces.
+    pub fn color(&self, color: Color) -> Bitboard {
+        self.by_color[color as usize]
+    }
+
+    /// The squares holding pieces of type `ty`, of either color.
+    pub fn piece_type(&self, ty: PieceType) -> Bitboard {
+        self.by_type[ty as usize]
+    }
+
+    /// The squares holding `piece`.
+    pub fn piece(&self, piece: Piece) -> Bitboard {
+        self.color(piece.color) & self.piece_type(piece.ty)
+    }
+}
+
+impl GameState {
+    /// Where the pieces are, kept in step with `board`.
+    pub fn occupancy(&self) -> &Occupancy {
+        &self.occupancy
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::sq;
+
+    #[test]
+    fn test_square_masks() {
+        assert_eq!(
+            HQS.squares().collect::<Vec<_>>(),
+            vec![sq::M2, sq::M4, sq::A2, sq::A4]
+        );
+        assert_eq!(CAMPS.count(), 10);
+        assert_eq!(RAIL_NETWORK.count(), 35);
+        assert!(!LANDABLE.contains(sq::G3) && !LANDABLE.contains(sq::G2));
+        assert_eq!(LANDABLE.count(), 60);
+        assert_eq!(!Bitboard::EMPTY, Bitboard::ALL);
+        assert!(!Bitboard::ALL.contains(NUM_SQUARES as SquareIndex));
+    }
+
+    #[test]
+    fn test_occupancy_follows_moves() {
+        let mut state = GameState::random_start(5);
+        assert_eq!(state.occupancy().occupied().count(), 50);
+        assert_eq!(
+            state.occupancy().piece(Piece {
+                ty: PieceType::Flag,
+                color: Color::Black
+            }),
+            Bitboard::square(state.flag_square(Color::Black).unwrap())
+        );
+
+        for _ in 0..40 {
+            let moves = state.legal_moves();
+            let Some(&mv) = moves.get(state.ply as usize % moves.len().max(1)) else {
+                break;
+            };
+            state.make_move_unchecked(mv);
+            assert_eq!(*state.occupancy(), Occupancy::from_board(&state.board));
+        }
+        while state.unmake_move().is_some() {
+            assert_eq!(*state.occupancy(), Occupancy::from_board(&state.board));
+        }
+        assert_eq!(state.ply, 0);
+    }
+}
@@ -1,4 +1,10 @@
+use crate::bitboard::Occupancy;
+use crate::history::MoveRecord;
+use crate::movegen::Move;
 use crate::prng::PseudoRng;
+use crate::rules::Rules;
+use crate::setup::{Deployment, SetupViolation};
+use crate::zobrist;
 use serde::{Deserialize, Serialize};
 
 pub const NUM_SQUARES: usize = 65;
@@ -131,12 +137,12 @@ pub mod sq {
 
 // Helper functions for squares
 
-const fn idx_to_rc(idx: SquareIndex) -> (i32, i32) {
+pub(crate) const fn idx_to_rc(idx: SquareIndex) -> (i32, i32) {
     let i = idx as usize;
     ((i / 5) as i32, (i % 5) as i32)
 }
 
-const fn rc_to_idx(r: i32, c: i32) -> Option<SquareIndex> {
+pub(crate) const fn rc_to_idx(r: i32, c: i32) -> Option<SquareIndex> {
     if 0 <= r && r < 13 && 0 <= c && c < 5 {
         Some((r as usize * 5 + c as usize) as SquareIndex)
     } else {
@@ -144,42 +150,42 @@ const fn rc_to_idx(r: i32, c: i32) -> Option<SquareIndex> {
     }
 }
 
-const fn up(sq: SquareIndex) -> Option<SquareIndex> {
+pub(crate) const fn up(sq: SquareIndex) -> Option<SquareIndex> {
     let (r, c) = idx_to_rc(sq);
     rc_to_idx(r - 1, c)
 }
 
-const fn down(sq: SquareIndex) -> Option<SquareIndex> {
+pub(crate) const fn down(sq: SquareIndex) -> Option<SquareIndex> {
     let (r, c) = idx_to_rc(sq);
     rc_to_idx(r + 1, c)
 }
 
-const fn left(sq: SquareIndex) -> Option<SquareIndex> {
+pub(crate) const fn left(sq: SquareIndex) -> Option<SquareIndex> {
     let (r, c) = idx_to_rc(sq);
     rc_to_idx(r, c - 1)
 }
 
-const fn right(sq: SquareIndex) -> Option<SquareIndex> {
+pub(crate) const fn right(sq: SquareIndex) -> Option<SquareIndex> {
     let (r, c) = idx_to_rc(sq);
     rc_to_idx(r, c + 1)
 }
 
-const fn up_left(sq: SquareIndex) -> Option<SquareIndex> {
+pub(crate) const fn up_left(sq: SquareIndex) -> Option<SquareIndex> {
     let (r, c) = idx_to_rc(sq);
     rc_to_idx(r - 1, c - 1)
 }
 
-const fn up_right(sq: SquareIndex) -> Option<SquareIndex> {
+pub(crate) const fn up_right(sq: SquareIndex) -> Option<SquareIndex> {
     let (r, c) = idx_to_rc(sq);
     rc_to_idx(r - 1, c + 1)
 }
 
-const fn down_left(sq: SquareIndex) -> Option<SquareIndex> {
+pub(crate) const fn down_left(sq: SquareIndex) -> Option<SquareIndex> {
     let (r, c) = idx_to_rc(sq);
     rc_to_idx(r + 1, c - 1)
 }
 
-const fn down_right(sq: SquareIndex) -> Option<SquareIndex> {
+pub(crate) const fn down_right(sq: SquareIndex) -> Option<SquareIndex> {
     let (r, c) = idx_to_rc(sq);
     rc_to_idx(r + 1, c + 1)
 }
@@ -192,7 +198,7 @@ const fn down_right(sq: SquareIndex) -> Option<SquareIndex> {
 /// Headquarters: pieces can move in, but can never move out (H)
 /// Frontline: acts like a railroad, but pieces cannot land on it (F)
 /// Mountain: cannot be used (M)
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u8)]
 pub enum SquareType {
     Empty,     // X
@@ -377,6 +383,32 @@ impl PieceType {
             PieceType::Flag => 1,
         }
     }
+
+    /// Returns the single-letter code of the piece type, as listed above.
+    pub fn to_char(&self) -> char {
+        match self {
+            PieceType::Overall => 'O',
+            PieceType::Army => 'A',
+            PieceType::Division => 'D',
+            PieceType::Brigade => 'B',
+            PieceType::Regiment => 'R',
+            PieceType::Battalion => 'T',
+            PieceType::Company => 'C',
+            PieceType::Platoon => 'P',
+            PieceType::Engineer => 'E',
+            PieceType::Bomb => 'X',
+            PieceType::Landmine => 'L',
+            PieceType::Flag => 'F',
+        }
+    }
+
+    /// Parses an uppercase single-letter code.
+    pub fn from_char(c: char) -> Option<Self> {
+        LIST_OF_PIECETYPES
+            .iter()
+            .copied()
+            .find(|ty| ty.to_char() == c)
+    }
 }
 
 /// Represents the color of a piece in the game.
@@ -404,178 +436,234 @@ pub struct Piece {
     pub color: Color,
 }
 
+impl Piece {
+    /// Returns the piece's letter: uppercase for Red, lowercase for Black.
+    pub fn to_char(&self) -> char {
+        match self.color {
+            Color::Red => self.ty.to_char(),
+            Color::Black => self.ty.to_char().to_ascii_lowercase(),
+        }
+    }
+
+    /// Parses a piece letter: uppercase for Red, lowercase for Black.
+    pub fn from_char(c: char) -> Option<Self> {
+        let color = if c.is_ascii_uppercase() {
+            Color::Red
+        } else {
+            Color::Black
+        };
+        PieceType::from_char(c.to_ascii_uppercase()).map(|ty| Piece { ty, color })
+    }
+}
+
 /// Represents the state of the game.
+///
+/// Edit `board` and `turn` through [`GameState::set_piece`] and
+/// [`GameState::set_turn`]. Code that writes them directly must call
+/// [`GameState::refresh`] afterwards to bring the Zobrist key and occupancy
+/// bitboards up to date.
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(try_from = "GameStateData")]
 pub struct GameState {
     pub turn: Color,
     pub board: Vec<Option<Piece>>,
+    /// Plies played since the start of the game.
+    pub ply: u32,
+    /// Plies played since the last attack, for the no-capture draw rule.
+    pub plies_since_capture: u32,
+    /// The rule variants the game is played with.
+    #[serde(default)]
+    pub rules: Rules,
+    /// A pending draw offer. The offer lapses when the other side moves instead
+    /// of accepting it.
+    pub draw_offer: Option<Color>,
+    /// Both sides agreed to a draw.
+    pub draw_agreed: bool,
+    /// Whether the identity of the piece on each square is known to both
+    /// players. The flag travels with the piece when it moves.
+    pub revealed: Vec<bool>,
+    /// Pieces removed from the board, in the order they were lost.
+    pub captured: Vec<Piece>,
+    /// Every move played so far, with what is needed to take it back.
+    #[serde(default)]
+    pub history: Vec<MoveRecord>,
+    /// Moves taken back with [`GameState::undo`], the next one to redo last.
+    #[serde(default)]
+    pub redo_stack: Vec<Move>,
+    /// Zobrist key of the position, updated as moves are made.
+    #[serde(skip)]
+    pub(crate) zobrist: u64,
+    /// Bitboards of the pieces on `board`, updated as moves are made.
+    #[serde(skip)]
+    pub(crate) occupancy: Occupancy,
+}
+
+/// The serialized fields of [`GameState`]. Only `turn` and `board` are
+/// required, so a bare board loads as a game that has not started. Cached
+/// data such as the Zobrist key and bitboards is rebuilt when a state is
+/// deserialized.
+#[derive(Deserialize)]
+struct GameStateData {
+    turn: Color,
+    board: Vec<Option<Piece>>,
+    #[serde(default)]
+    ply: u32,
+    #[serde(default)]
+    plies_since_capture: u32,
+    #[serde(default)]
+    rules: Rules,
+    #[serde(default)]
+    draw_offer: Option<Color>,
+    #[serde(default)]
+    draw_agreed: bool,
+    #[serde(default)]
+    revealed: Vec<bool>,
+    #[serde(default)]
+    captured: Vec<Piece>,
+    #[serde(default)]
+    history: Vec<MoveRecord>,
+    #[serde(default)]
+    redo_stack: Vec<Move>,
+}
+
+impl TryFrom<GameStateData> for GameState {
+    type Error = String;
+
+    fn try_from(mut data: GameStateData) -> Result<Self, Self::Error> {
+        if data.board.len() != NUM_SQUARES {
+            return Err(format!(
+                "board has {} squares, expected {NUM_SQUARES}",
+                data.board.len()
+            ));
+        }
+        if data.revealed.is_empty() {
+            data.revealed = vec![false; NUM_SQUARES];
+        } else if data.revealed.len() != NUM_SQUARES {
+            return Err(format!(
+                "revealed has {} squares, expected {NUM_SQUARES}",
+                data.revealed.len()
+            ));
+        }
+        let mut moves = data
+            .history
+            .iter()
+            .map(|r| r.mv)
+            .chain(data.redo_stack.iter().copied());
+        if let Some(mv) =
+            moves.find(|mv| mv.from as usize >= NUM_SQUARES || mv.to as usize >= NUM_SQUARES)
+        {
+            return Err(format!("move {mv} is off the board"));
+        }
+        let mut state = GameState {
+            turn: data.turn,
+            board: data.board,
+            ply: data.ply,
+            plies_since_capture: data.plies_since_capture,
+            rules: data.rules,
+            draw_offer: data.draw_offer,
+            draw_agreed: data.draw_agreed,
+            revealed: data.revealed,
+            captured: data.captured,
+            history: data.history,
+            redo_stack: data.redo_stack,
+            zobrist: 0,
+            occupancy: Occupancy::default(),
+        };
+        state.refresh();
+        Ok(state)
+    }
+}
+
+/// Default number of plies without an attack before the game is drawn.
+pub const DEFAULT_NO_CAPTURE_LIMIT: u32 = 100;
+
+impl Default for GameState {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl GameState {
     pub fn new() -> Self {
+        Self::with_rules(Rules::default())
+    }
+
+    /// An empty board for a game played with `rules`.
+    pub fn with_rules(rules: Rules) -> Self {
         Self {
             turn: Color::Red,
             board: vec![None; NUM_SQUARES],
+            ply: 0,
+            plies_since_capture: 0,
+            rules,
+            draw_offer: None,
+            draw_agreed: false,
+            revealed: vec![false; NUM_SQUARES],
+            captured: Vec::new(),
+            history: Vec::new(),
+            redo_stack: Vec::new(),
+            zobrist: 0,
+            occupancy: Occupancy::default(),
         }
     }
 
+    /// A board holding only `pieces`, for setting up test positions.
+    #[cfg(test)]
+    pub(crate) fn from_pieces(pieces: &[(SquareIndex, PieceType, Color)]) -> Self {
+        let mut state = GameState::new();
+        for &(sq, ty, color) in pieces {
+            state.set_piece(sq, Some(Piece { ty, color }));
+        }
+        state
+    }
+
     /// Generate a random legal starting position using the provided seed.
     pub fn random_start(seed: u64) -> Self {
-        let mut rng = PseudoRng::new(seed);
-        let mut state = GameState::new();
+        Self::random_start_with_rules(seed, Rules::default())
+            .expect("the standard pieces fit on the board")
+    }
 
-        for &color in &[Color::Red, Color::Black] {
-            let mut available: Vec<SquareIndex> = if color == Color::Red {
-                sq::RED_SIDE.to_vec()
-            } else {
-                sq::BLACK_SIDE.to_vec()
-            };
-
-            // Flag placed on a HQ
-            let hqs = if color == Color::Red {
-                sq::RED_HQ
-            } else {
-                sq::BLACK_HQ
-            };
-            let flag_sq = hqs[rng.gen_range(0..hqs.len())];
-            state.board[flag_sq as usize] = Some(Piece {
-                ty: PieceType::Flag,
-                color,
-            });
-            available.retain(|&s| s != flag_sq);
-
-            // Landmines placed on back rows
-            let back_rows = if color == Color::Red {
-                sq::RED_BACK_ROWS
-            } else {
-                sq::BLACK_BACK_ROWS
-            };
-            for _ in 0..PieceType::Landmine.num_per_player() {
-                let choices: Vec<usize> = available
-                    .iter()
-                    .enumerate()
-                    .filter(|(_, s)| back_rows.contains(s))
-                    .map(|(i, _)| i)
-                    .collect();
-                let idx = choices[rng.gen_range(0..choices.len())];
-                let sq = available.remove(idx);
-                state.board[sq as usize] = Some(Piece {
-                    ty: PieceType::Landmine,
-                    color,
-                });
-            }
-
-            // Bombs not allowed on the first row
-            let first_row = if color == Color::Red {
-                sq::RED_FIRST_ROW
-            } else {
-                sq::BLACK_FIRST_ROW
-            };
-            for _ in 0..PieceType::Bomb.num_per_player() {
-                let choices: Vec<usize> = available
-                    .iter()
-                    .enumerate()
-                    .filter(|(_, s)| !first_row.contains(s))
-                    .map(|(i, _)| i)
-                    .collect();
-                let idx = choices[rng.gen_range(0..choices.len())];
-                let sq = available.remove(idx);
-                state.board[sq as usize] = Some(Piece {
-                    ty: PieceType::Bomb,
-                    color,
-                });
-            }
-
-            // Remaining piece types
-            let mut remaining: Vec<PieceType> = Vec::new();
-            for ty in LIST_OF_PIECETYPES.iter() {
-                if *ty == PieceType::Flag || *ty == PieceType::Bomb || *ty == PieceType::Landmine {
-                    continue;
-                }
-                for _ in 0..ty.num_per_player() {
-                    remaining.push(*ty);
-                }
-            }
-
-            while !remaining.is_empty() {
-                let sq_idx = rng.gen_range(0..available.len());
-                let sq = available.remove(sq_idx);
-                let ty_idx = rng.gen_range(0..remaining.len());
-                let ty = remaining.remove(ty_idx);
-                state.board[sq as usize] = Some(Piece { ty, color });
-            }
-        }
+    /// Generate a random starting position that is legal under `rules`.
+    /// Fails if the piece counts in `rules` leave no room for one.
+    pub fn random_start_with_rules(seed: u64, rules: Rules) -> Result<Self, SetupViolation> {
+        let mut rng = PseudoRng::new(seed);
+        let red = Deployment::random(Color::Red, &rules, &mut rng)?;
+        let black = Deployment::random(Color::Black, &rules, &mut rng)?;
 
-        state
+        let mut state = GameState::with_rules(rules);
+        state.place_deployment(&red);
+        state.place_deployment(&black);
+        Ok(state)
     }
 
-    pub fn is_startpos_legal(&self) -> bool {
-        if self.board.len() != NUM_SQUARES {
-            return false;
-        }
+    /// Recomputes cached data, such as the Zobrist key and occupancy
+    /// bitboards, after `board` or `turn` were edited directly.
+    pub fn refresh(&mut self) {
+        self.zobrist = self.compute_zobrist_key();
+        self.occupancy = Occupancy::from_board(&self.board);
+    }
 
-        // piece counts per color
-        let mut counts = [[0; NUM_PIECETYPES]; 2];
-        for opt in &self.board {
-            if let Some(p) = opt {
-                counts[p.color as usize][p.ty as usize] += 1;
-            }
-        }
-        for color in [Color::Red, Color::Black] {
-            for (i, ty) in LIST_OF_PIECETYPES.iter().enumerate() {
-                if counts[color as usize][i] != ty.num_per_player() {
-                    return false;
-                }
-            }
-        }
+    /// Puts `piece` on `sq`, or empties it for `None`, keeping the cached
+    /// Zobrist key and occupancy bitboards up to date.
+    pub fn set_piece(&mut self, sq: SquareIndex, piece: Option<Piece>) {
+        let old = std::mem::replace(&mut self.board[sq as usize], piece);
+        self.zobrist ^= zobrist::square_key(sq, old) ^ zobrist::square_key(sq, piece);
+        self.occupancy.toggle(sq, old);
+        self.occupancy.toggle(sq, piece);
+    }
 
-        for (idx, opt) in self.board.iter().enumerate() {
-            let piece = match opt {
-                Some(p) => p,
-                None => continue,
-            };
-            let sq = idx as SquareIndex;
-
-            // pieces only on Empty/Railroad/HQ
-            match SQUARE_TO_SQUARETYPE[idx] {
-                SquareType::Empty | SquareType::Railroad | SquareType::HQ => {}
-                _ => return false,
-            }
-
-            let (side, hq, first_row, back_rows) = if piece.color == Color::Red {
-                (
-                    &sq::RED_SIDE[..],
-                    &sq::RED_HQ[..],
-                    &sq::RED_FIRST_ROW[..],
-                    &sq::RED_BACK_ROWS[..],
-                )
-            } else {
-                (
-                    &sq::BLACK_SIDE[..],
-                    &sq::BLACK_HQ[..],
-                    &sq::BLACK_FIRST_ROW[..],
-                    &sq::BLACK_BACK_ROWS[..],
-                )
-            };
-
-            if !side.contains(&sq) {
-                return false;
-            }
-
-            if piece.ty == PieceType::Bomb && first_row.contains(&sq) {
-                return false;
-            }
-
-            if piece.ty == PieceType::Landmine && !back_rows.contains(&sq) {
-                return false;
-            }
-
-            if piece.ty == PieceType::Flag && !hq.contains(&sq) {
-                return false;
-            }
+    /// Sets the side to move, keeping the cached Zobrist key up to date.
+    pub fn set_turn(&mut self, turn: Color) {
+        if turn != self.turn {
+            self.turn = turn;
+            self.zobrist ^= zobrist::side_key();
         }
+    }
 
-        true
+    /// Returns whether the position is a legal start. See
+    /// [`GameState::validate_startpos`] for the reasons it is not.
+    pub fn is_startpos_legal(&self) -> bool {
+        self.validate_startpos().is_empty()
     }
 }
 
@@ -601,4 +689,22 @@ mod tests {
         });
         assert!(!state.is_startpos_legal());
     }
+
+    #[test]
+    fn test_deserialize_bare_board() {
+        let state = GameState::random_start(6);
+        let board = serde_json::to_string(&state.board).unwrap();
+        let json = format!(r#"{{"turn":"Black","board":{board}}}"#);
+        let loaded: GameState = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.board, state.board);
+        assert_eq!(loaded.turn, Color::Black);
+        assert_eq!(loaded.ply, 0);
+        assert_eq!(loaded.revealed, vec![false; NUM_SQUARES]);
+
+        let short = r#"{"turn":"Red","board":[null,null]}"#;
+        let err = serde_json::from_str::<GameState>(short).unwrap_err();
+        assert!(err.to_string().contains("board has 2 squares"));
+        let json = format!(r#"{{"turn":"Red","board":{board},"revealed":[true]}}"#);
+        assert!(serde_json::from_str::<GameState>(&json).is_err());
+    }
 }
@@ -0,0 +1,285 @@
+use crate::board::{GameState, Piece, PieceType};
+use crate::history::MoveRecord;
+use crate::movegen::Move;
+use crate::repetition::RepetitionViolation;
+use crate::zobrist;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// What happens when one piece attacks another.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CombatResult {
+    /// The defender is removed and the attacker takes its square.
+    AttackerWins,
+    /// The attacker is removed; the defender stays.
+    DefenderWins,
+    /// Both pieces are removed.
+    BothRemoved,
+}
+
+/// The outcome of a move, for clients to animate and log.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MoveOutcome {
+    /// The piece moved to an empty square.
+    Moved,
+    /// The piece attacked `defender` with the given result.
+    Attack {
+        attacker: Piece,
+        defender: Piece,
+        result: CombatResult,
+    },
+    /// The piece attacked and captured the enemy flag.
+    FlagCaptured { attacker: Piece, flag: Piece },
+}
+
+/// Reasons a move can be rejected by [`GameState::make_move`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MoveError {
+    /// There is no piece on the starting square.
+    NoPiece,
+    /// The piece on the starting square belongs to the side not on move.
+    NotYourTurn,
+    /// The piece cannot reach the target square, or the target is occupied
+    /// by a friendly piece or a piece in a camp.
+    Illegal,
+    /// The move breaks one of the game's repetition limits.
+    Repetition(RepetitionViolation),
+}
+
+impl fmt::Display for MoveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MoveError::NoPiece => write!(f, "no piece on the starting square"),
+            MoveError::NotYourTurn => write!(f, "piece does not belong to the side to move"),
+            MoveError::Illegal => write!(f, "illegal move"),
+            MoveError::Repetition(v) => write!(f, "{v}"),
+        }
+    }
+}
+
+impl std::error::Error for MoveError {}
+
+impl GameState {
+    /// Checks that `mv` is legal for the side to move. The repetition limits
+    /// are checked by [`GameState::repetition_violation`].
+    pub fn check_move(&self, mv: Move) -> Result<(), MoveError> {
+        let piece = self.board[mv.from as usize].ok_or(MoveError::NoPiece)?;
+        if piece.color != self.turn {
+            return Err(MoveError::NotYourTurn);
+        }
+        if !self.moves_from(mv.from).contains(&mv) {
+            return Err(MoveError::Illegal);
+        }
+        Ok(())
+    }
+
+    /// Plays `mv` for the side to move, resolving any combat. The move must
+    /// also be within the repetition limits. Moves taken back with
+    /// [`GameState::undo`] can no longer be redone afterwards.
+    pub fn make_move(&mut self, mv: Move) -> Result<MoveOutcome, MoveError> {
+        self.check_move(mv)?;
+        if let Some(violation) = self.repetition_violation(mv) {
+            return Err(MoveError::Repetition(violation));
+        }
+        self.redo_stack.clear();
+        Ok(self.make_move_unchecked(mv))
+    }
+
+    /// Plays `mv` without checking that it is legal. Only call this with moves
+    /// obtained from [`GameState::legal_moves`]. The move is recorded in
+    /// `history` so that [`GameState::unmake_move`] can take it back.
+    pub fn make_move_unchecked(&mut self, mv: Move) -> MoveOutcome {
+        let (from, to) = (mv.from as usize, mv.to as usize);
+        let mut record = MoveRecord {
+            mv,
+            outcome: MoveOutcome::Moved,
+            key: self.zobrist,
+            from_revealed: self.revealed[from],
+            to_piece: self.board[to],
+            to_revealed: self.revealed[to],
+            plies_since_capture: self.plies_since_capture,
+            draw_offer: self.draw_offer,
+            flags_revealed: [false; 2],
+        };
+        let attacker = self.board[from]
+            .take()
+            .expect("no piece on the starting square");
+        let attacker_revealed = std::mem::take(&mut self.revealed[from]);
+
+        let key_before = zobrist::square_key(mv.from, Some(attacker))
+            ^ zobrist::square_key(mv.to, self.board[to]);
+
+        let outcome = match self.board[to] {
+            None => {
+                self.board[to] = Some(attacker);
+                self.revealed[to] = attacker_revealed;
+                MoveOutcome::Moved
+            }
+            Some(defender) => {
+                // Combat is public: whichever piece survives is revealed
+                let result = self.rules.resolve_combat(attacker.ty, defender.ty);
+                match result {
+                    CombatResult::AttackerWins => {
+                        self.board[to] = Some(attacker);
+                        self.revealed[to] = true;
+                        self.captured.push(defender);
+                    }
+                    CombatResult::DefenderWins => {
+                        self.revealed[to] = true;
+                        self.captured.push(attacker);
+                    }
+                    CombatResult::BothRemoved => {
+                        self.board[to] = None;
+                        self.revealed[to] = false;
+                        self.captured.push(attacker);
+                        self.captured.push(defender);
+                    }
+                }
+                // Losing the field marshal reveals the flag to the opponent
+                for p in [attacker, defender] {
+                    if p.ty == PieceType::Overall
+                        && self.board[to] != Some(p)
+                        && !self.flag_revealed(p.color)
+                    {
+                        self.reveal_flag(p.color);
+                        record.flags_revealed[p.color as usize] = true;
+                    }
+                }
+                if defender.ty == PieceType::Flag && result != CombatResult::DefenderWins {
+                    MoveOutcome::FlagCaptured {
+                        attacker,
+                        flag: defender,
+                    }
+                } else {
+                    MoveOutcome::Attack {
+                        attacker,
+                        defender,
+                        result,
+                    }
+                }
+            }
+        };
+
+        self.ply += 1;
+        if outcome == MoveOutcome::Moved {
+            self.plies_since_capture += 1;
+        } else {
+            self.plies_since_capture = 0;
+        }
+        // Moving instead of accepting declines the opponent's draw offer
+        if self.draw_offer == Some(self.turn.other()) {
+            self.draw_offer = None;
+        }
+        self.turn = self.turn.other();
+        self.zobrist ^=
+            key_before ^ zobrist::square_key(mv.to, self.board[to]) ^ zobrist::side_key();
+        self.occupancy.toggle(mv.from, Some(attacker));
+        self.occupancy.toggle(mv.to, record.to_piece);
+        self.occupancy.toggle(mv.to, self.board[to]);
+        record.outcome = outcome;
+        self.history.push(record);
+        outcome
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::{sq, Color};
+    use crate::rules::Rules;
+
+    #[test]
+    fn test_resolve_combat() {
+        use CombatResult::*;
+        use PieceType::*;
+        let rules = Rules::default();
+        let resolve_combat = |a, d| rules.resolve_combat(a, d);
+        assert_eq!(resolve_combat(Army, Division), AttackerWins);
+        assert_eq!(resolve_combat(Platoon, Company), DefenderWins);
+        assert_eq!(resolve_combat(Brigade, Brigade), BothRemoved);
+        assert_eq!(resolve_combat(Bomb, Overall), BothRemoved);
+        assert_eq!(resolve_combat(Engineer, Bomb), BothRemoved);
+        assert_eq!(resolve_combat(Overall, Landmine), DefenderWins);
+        assert_eq!(resolve_combat(Engineer, Landmine), AttackerWins);
+        assert_eq!(resolve_combat(Bomb, Landmine), BothRemoved);
+        assert_eq!(resolve_combat(Engineer, Flag), AttackerWins);
+    }
+
+    #[test]
+    fn test_make_move_applies_combat() {
+        let mut state = GameState::new();
+        state.set_piece(
+            sq::F1,
+            Some(Piece {
+                ty: PieceType::Army,
+                color: Color::Red,
+            }),
+        );
+        state.set_piece(
+            sq::H1,
+            Some(Piece {
+                ty: PieceType::Overall,
+                color: Color::Black,
+            }),
+        );
+
+        let outcome = state.make_move(Move::new(sq::F1, sq::H1)).unwrap();
+        assert_eq!(
+            outcome,
+            MoveOutcome::Attack {
+                attacker: Piece {
+                    ty: PieceType::Army,
+                    color: Color::Red
+                },
+                defender: Piece {
+                    ty: PieceType::Overall,
+                    color: Color::Black
+                },
+                result: CombatResult::DefenderWins,
+            }
+        );
+        assert_eq!(state.board[sq::F1 as usize], None);
+        assert_eq!(state.board[sq::H1 as usize].unwrap().ty, PieceType::Overall);
+        assert_eq!(state.turn, Color::Black);
+    }
+
+    #[test]
+    fn test_make_move_rejects_illegal_moves() {
+        let mut state = GameState::random_start(7);
+        assert_eq!(
+            state.make_move(Move::new(sq::G2, sq::F2)),
+            Err(MoveError::NoPiece)
+        );
+        assert_eq!(
+            state.make_move(Move::new(sq::L1, sq::K1)),
+            Err(MoveError::NotYourTurn)
+        );
+        assert_eq!(
+            state.make_move(Move::new(sq::A1, sq::M1)),
+            Err(MoveError::Illegal)
+        );
+    }
+
+    #[test]
+    fn test_flag_capture() {
+        let mut state = GameState::new();
+        state.set_turn(Color::Black);
+        state.set_piece(
+            sq::B2,
+            Some(Piece {
+                ty: PieceType::Engineer,
+                color: Color::Black,
+            }),
+        );
+        state.set_piece(
+            sq::A2,
+            Some(Piece {
+                ty: PieceType::Flag,
+                color: Color::Red,
+            }),
+        );
+        let outcome = state.make_move(Move::new(sq::B2, sq::A2)).unwrap();
+        assert!(matches!(outcome, MoveOutcome::FlagCaptured { .. }));
+        assert_eq!(state.board[sq::A2 as usize].unwrap().color, Color::Black);
+    }
+}
@@ -0,0 +1,481 @@
+//! Four-player game state, moves and results.
+
+use super::geometry::{arm_square, geometry};
+use super::{FourColor, FourSquare, Team, NUM_FOUR_SQUARES};
+use crate::board::{Color, PieceType, SquareType};
+use crate::combat::{CombatResult, MoveError};
+use crate::movegen::Move;
+use crate::prng::PseudoRng;
+use crate::result::DrawReason;
+use crate::rules::Rules;
+use crate::setup::{Deployment, SetupViolation};
+use crate::square::Square;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FourPiece {
+    pub ty: PieceType,
+    pub color: FourColor,
+}
+
+/// The state of a four-player game from the referee's point of view.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FourResult {
+    Ongoing,
+    Won(Team),
+    Draw(DrawReason),
+}
+
+/// A four-player game.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FourPlayerGame {
+    pub turn: FourColor,
+    pub board: Vec<Option<FourPiece>>,
+    /// Players who are out of the game, in the order they went out.
+    pub out: Vec<FourColor>,
+    /// Plies played since the start of the game.
+    pub ply: u32,
+    /// Plies played since the last attack, for the no-capture draw rule.
+    pub plies_since_capture: u32,
+    /// Pieces removed in combat, in the order they were lost. Pieces taken
+    /// off when a player goes out are not included.
+    pub captured: Vec<FourPiece>,
+    pub rules: Rules,
+}
+
+impl Default for FourPlayerGame {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FourPlayerGame {
+    pub fn new() -> Self {
+        Self::with_rules(Rules::default())
+    }
+
+    /// An empty board for a game played with `rules`.
+    pub fn with_rules(rules: Rules) -> Self {
+        Self {
+            turn: FourColor::Red,
+            board: vec![None; NUM_FOUR_SQUARES],
+            out: Vec::new(),
+            ply: 0,
+            plies_since_capture: 0,
+            captured: Vec::new(),
+            rules,
+        }
+    }
+
+    /// Generate a random legal starting position using the provided seed.
+    pub fn random_start(seed: u64) -> Self {
+        Self::random_start_with_rules(seed, Rules::default())
+            .expect("the standard pieces fit on the board")
+    }
+
+    /// Generate a random starting position that is legal under `rules`.
+    /// Fails if the piece counts in `rules` leave no room for one.
+    pub fn random_start_with_rules(seed: u64, rules: Rules) -> Result<Self, SetupViolation> {
+        let mut rng = PseudoRng::new(seed);
+        let mut game = Self::with_rules(rules);
+        for color in FourColor::ALL {
+            let deployment = Deployment::random(Color::Red, &rules, &mut rng)?;
+            game.place_deployment(color, &deployment);
+        }
+        Ok(game)
+    }
+
+    /// Starts a game from each player's deployment, given in [`FourColor`]
+    /// order. An arm has the same shape as one side of the two-player board,
+    /// so each deployment is laid out and validated as a two-player one, as
+    /// seen by its owner.
+    pub fn from_deployments(
+        deployments: [&Deployment; 4],
+        rules: Rules,
+    ) -> Result<Self, Vec<(FourColor, SetupViolation)>> {
+        let mut game = Self::with_rules(rules);
+        let mut violations = Vec::new();
+        for (color, deployment) in FourColor::ALL.into_iter().zip(deployments) {
+            violations.extend(deployment.validate(&rules).into_iter().map(|v| (color, v)));
+            game.place_deployment(color, deployment);
+        }
+        if violations.is_empty() {
+            Ok(game)
+        } else {
+            Err(violations)
+        }
+    }
+
+    fn place_deployment(&mut self, color: FourColor, deployment: &Deployment) {
+        let deployment = match deployment.color() {
+            Color::Red => deployment.clone(),
+            Color::Black => deployment.rotated(),
+        };
+        for (sq, piece) in deployment.pieces() {
+            let square = Square::new(sq).unwrap();
+            self.board[arm_square(color, square.row(), square.col()) as usize] = Some(FourPiece {
+                ty: piece.ty,
+                color,
+            });
+        }
+    }
+
+    /// `color`'s pieces on its own arm, laid out as a Red two-player
+    /// deployment.
+    pub fn arm_deployment(&self, color: FourColor) -> Deployment {
+        let mut deployment = Deployment::new(Color::Red);
+        for row in 0..6 {
+            for col in 0..5 {
+                let piece = self.board[arm_square(color, row, col) as usize];
+                if let Some(p) = piece.filter(|p| p.color == color) {
+                    let sq = Square::from_row_col(row, col).unwrap();
+                    deployment.place(sq.index(), p.ty);
+                }
+            }
+        }
+        deployment
+    }
+
+    /// Whether `color` is out of the game.
+    pub fn is_out(&self, color: FourColor) -> bool {
+        self.out.contains(&color)
+    }
+
+    /// Returns every legal move for the player to move.
+    pub fn legal_moves(&self) -> Vec<Move> {
+        let mut moves = Vec::new();
+        for from in 0..NUM_FOUR_SQUARES as FourSquare {
+            if matches!(self.board[from as usize], Some(p) if p.color == self.turn) {
+                self.gen_piece_moves(from, &mut moves);
+            }
+        }
+        moves
+    }
+
+    /// Returns the legal moves of the piece on `from`, regardless of whose turn it is.
+    pub fn moves_from(&self, from: FourSquare) -> Vec<Move> {
+        let mut moves = Vec::new();
+        self.gen_piece_moves(from, &mut moves);
+        moves
+    }
+
+    /// Whether `color` may end a move on `to`: the square must be empty or
+    /// hold an opposing piece outside a camp.
+    fn can_land(&self, color: FourColor, to: FourSquare) -> bool {
+        match self.board[to as usize] {
+            None => true,
+            Some(_) if geometry().square_types[to as usize] == SquareType::Camp => false,
+            Some(p) => p.color.team() != color.team(),
+        }
+    }
+
+    fn gen_piece_moves(&self, from: FourSquare, moves: &mut Vec<Move>) {
+        let geo = geometry();
+        let piece = match self.board[from as usize] {
+            Some(p) => p,
+            None => return,
+        };
+        if matches!(piece.ty, PieceType::Landmine | PieceType::Flag)
+            || geo.square_types[from as usize] == SquareType::HQ
+        {
+            return;
+        }
+
+        let mut seen = [false; NUM_FOUR_SQUARES];
+        let mut push = |to: FourSquare, moves: &mut Vec<Move>| {
+            if !seen[to as usize] && self.can_land(piece.color, to) {
+                seen[to as usize] = true;
+                moves.push(Move::new(from, to));
+            }
+        };
+
+        for &to in &geo.adjacent[from as usize] {
+            push(to, moves);
+        }
+
+        if !geo.is_rail(from) {
+            return;
+        }
+
+        if piece.ty == PieceType::Engineer {
+            // Engineers may travel anywhere along connected railroad
+            let mut visited = [false; NUM_FOUR_SQUARES];
+            let mut queue = vec![from];
+            visited[from as usize] = true;
+            while let Some(sq) = queue.pop() {
+                for &to in &geo.rail_links[sq as usize] {
+                    if visited[to as usize] {
+                        continue;
+                    }
+                    visited[to as usize] = true;
+                    push(to, moves);
+                    if self.board[to as usize].is_none() {
+                        queue.push(to);
+                    }
+                }
+            }
+        } else {
+            // Other pieces move any distance in a straight line along the railroad
+            for line in &geo.rail_lines {
+                let pos = match line.iter().position(|&s| s == from) {
+                    Some(pos) => pos,
+                    None => continue,
+                };
+                for dir in [1, -1] {
+                    let mut i = pos as isize + dir;
+                    while let Some(&to) = line.get(i as usize) {
+                        push(to, moves);
+                        if self.board[to as usize].is_some() {
+                            break;
+                        }
+                        i += dir;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Plays `mv` for the player to move. Returns the result of the attack,
+    /// or `None` if the piece moved to an empty square.
+    pub fn make_move(&mut self, mv: Move) -> Result<Option<CombatResult>, MoveError> {
+        let piece = self.board[mv.from as usize].ok_or(MoveError::NoPiece)?;
+        if piece.color != self.turn {
+            return Err(MoveError::NotYourTurn);
+        }
+        if !self.moves_from(mv.from).contains(&mv) {
+            return Err(MoveError::Illegal);
+        }
+        Ok(self.make_move_unchecked(mv))
+    }
+
+    /// Plays `mv` without checking that it is legal, then takes out any
+    /// player who lost their flag or can no longer move and passes the turn
+    /// on.
+    pub fn make_move_unchecked(&mut self, mv: Move) -> Option<CombatResult> {
+        let (from, to) = (mv.from as usize, mv.to as usize);
+        let attacker = self.board[from]
+            .take()
+            .expect("no piece on the starting square");
+
+        let result = self.board[to].map(|defender| {
+            let result = self.rules.resolve_combat(attacker.ty, defender.ty);
+            match result {
+                CombatResult::AttackerWins => {
+                    self.board[to] = Some(attacker);
+                    self.captured.push(defender);
+                }
+                CombatResult::DefenderWins => self.captured.push(attacker),
+                CombatResult::BothRemoved => {
+                    self.board[to] = None;
+                    self.captured.push(attacker);
+                    self.captured.push(defender);
+                }
+            }
+            result
+        });
+        if result.is_none() {
+            self.board[to] = Some(attacker);
+        }
+
+        self.ply += 1;
+        if result.is_some() {
+            self.plies_since_capture = 0;
+        } else {
+            self.plies_since_capture += 1;
+        }
+
+        for color in FourColor::ALL {
+            if self.is_out(color) {
+                continue;
+            }
+            if !self.has_flag(color) || !self.has_movable_pieces(color) {
+                self.knock_out(color);
+            }
+        }
+        self.pass_turn();
+        result
+    }
+
+    /// Passes the turn to the next player still in the game. A player who
+    /// has no legal move when their turn comes is out.
+    fn pass_turn(&mut self) {
+        for _ in 0..4 {
+            if self.result().is_over() {
+                return;
+            }
+            self.turn = self.turn.next();
+            if self.is_out(self.turn) {
+                continue;
+            }
+            if !self.legal_moves().is_empty() {
+                return;
+            }
+            self.knock_out(self.turn);
+        }
+    }
+
+    /// Takes `color` out of the game and removes its pieces from the board.
+    fn knock_out(&mut self, color: FourColor) {
+        self.out.push(color);
+        for sq in self.board.iter_mut() {
+            if sq.is_some_and(|p| p.color == color) {
+                *sq = None;
+            }
+        }
+    }
+
+    /// Returns whether `color` still has its flag on the board.
+    pub fn has_flag(&self, color: FourColor) -> bool {
+        self.board
+            .iter()
+            .flatten()
+            .any(|p| p.color == color && p.ty == PieceType::Flag)
+    }
+
+    /// Returns whether `color` has any piece that counts as movable under the
+    /// rules.
+    pub fn has_movable_pieces(&self, color: FourColor) -> bool {
+        self.board
+            .iter()
+            .flatten()
+            .any(|p| p.color == color && self.rules.is_movable(p.ty))
+    }
+
+    /// Determines whether the game is over, and how.
+    pub fn result(&self) -> FourResult {
+        let team_out = |team: Team| team.members().iter().all(|&c| self.is_out(c));
+        match (team_out(Team::RedGreen), team_out(Team::YellowBlue)) {
+            (true, true) => return FourResult::Draw(DrawReason::BothTeamsOut),
+            (true, false) => return FourResult::Won(Team::YellowBlue),
+            (false, true) => return FourResult::Won(Team::RedGreen),
+            (false, false) => {}
+        }
+        if self.plies_since_capture >= self.rules.no_capture_limit {
+            return FourResult::Draw(DrawReason::NoCaptureLimit);
+        }
+        FourResult::Ongoing
+    }
+}
+
+impl FourResult {
+    pub fn is_over(&self) -> bool {
+        *self != FourResult::Ongoing
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::four::geometry::centre_square;
+
+    fn place(game: &mut FourPlayerGame, sq: FourSquare, ty: PieceType, color: FourColor) {
+        game.board[sq as usize] = Some(FourPiece { ty, color });
+    }
+
+    /// Every player has a flag in a headquarters and one army on its back
+    /// railroad row.
+    fn minimal() -> FourPlayerGame {
+        let mut game = FourPlayerGame::new();
+        for color in FourColor::ALL {
+            place(&mut game, arm_square(color, 0, 1), PieceType::Flag, color);
+            place(&mut game, arm_square(color, 1, 2), PieceType::Army, color);
+        }
+        game
+    }
+
+    #[test]
+    fn test_random_start() {
+        let game = FourPlayerGame::random_start(7);
+        assert_eq!(game.board.iter().flatten().count(), 100);
+        for color in FourColor::ALL {
+            assert_eq!(game.arm_deployment(color).validate(&game.rules), vec![]);
+        }
+        assert!(!game.legal_moves().is_empty());
+        assert_eq!(game.result(), FourResult::Ongoing);
+
+        let deployments = FourColor::ALL.map(|c| game.arm_deployment(c));
+        let rebuilt =
+            FourPlayerGame::from_deployments(deployments.each_ref(), Rules::default()).unwrap();
+        assert_eq!(rebuilt.board, game.board);
+    }
+
+    #[test]
+    fn test_rail_moves_cross_the_centre() {
+        let mut game = minimal();
+        let from = arm_square(FourColor::Red, 5, 0);
+        place(&mut game, from, PieceType::Division, FourColor::Red);
+        let green = arm_square(FourColor::Green, 3, 4);
+        place(&mut game, green, PieceType::Platoon, FourColor::Green);
+        let blue = arm_square(FourColor::Blue, 3, 4);
+        place(&mut game, blue, PieceType::Platoon, FourColor::Blue);
+
+        let targets: Vec<_> = game.moves_from(from).iter().map(|m| m.to).collect();
+        assert!(targets.contains(&centre_square(0, 2)));
+        assert!(targets.contains(&arm_square(FourColor::Green, 4, 4)));
+        // A partner's piece blocks the line and cannot be attacked
+        assert!(!targets.contains(&green));
+        // The curved railroad leads up Blue's last column to an enemy piece
+        assert!(targets.contains(&arm_square(FourColor::Blue, 5, 4)));
+        assert!(targets.contains(&blue));
+        assert!(!targets.contains(&arm_square(FourColor::Blue, 2, 4)));
+
+        // The back railroad row does not turn the corner
+        let corner = arm_square(FourColor::Red, 5, 4);
+        let targets: Vec<_> = game
+            .moves_from(arm_square(FourColor::Red, 1, 2))
+            .iter()
+            .map(|m| m.to)
+            .collect();
+        assert!(targets.contains(&arm_square(FourColor::Red, 1, 4)));
+        assert!(!targets.contains(&corner));
+    }
+
+    #[test]
+    fn test_turn_order_skips_players_who_are_out() {
+        let mut game = minimal();
+        // Red's army takes Yellow's flag from the neighbouring square
+        let flag = arm_square(FourColor::Yellow, 0, 1);
+        place(
+            &mut game,
+            arm_square(FourColor::Yellow, 1, 1),
+            PieceType::Army,
+            FourColor::Red,
+        );
+        game.board[arm_square(FourColor::Red, 1, 2) as usize] = None;
+        let result = game
+            .make_move(Move::new(arm_square(FourColor::Yellow, 1, 1), flag))
+            .unwrap();
+        assert_eq!(result, Some(CombatResult::AttackerWins));
+        assert_eq!(game.out, vec![FourColor::Yellow]);
+        assert!(game
+            .board
+            .iter()
+            .flatten()
+            .all(|p| p.color != FourColor::Yellow));
+        assert_eq!(game.turn, FourColor::Green);
+        assert_eq!(game.result(), FourResult::Ongoing);
+
+        // Blue's only army sits in its HQ, which pieces cannot leave, so Blue
+        // has no legal move and goes out on its turn
+        let blue_army = arm_square(FourColor::Blue, 1, 2);
+        game.board[blue_army as usize] = None;
+        place(
+            &mut game,
+            arm_square(FourColor::Blue, 0, 3),
+            PieceType::Army,
+            FourColor::Blue,
+        );
+        for col in [2, 3, 4] {
+            let sq = arm_square(FourColor::Blue, if col == 3 { 1 } else { 0 }, col);
+            place(&mut game, sq, PieceType::Landmine, FourColor::Blue);
+        }
+        let green_army = arm_square(FourColor::Green, 1, 2);
+        game.make_move(Move::new(green_army, green_army - 1))
+            .unwrap();
+        assert_eq!(game.out, vec![FourColor::Yellow, FourColor::Blue]);
+        assert_eq!(game.result(), FourResult::Won(Team::RedGreen));
+
+        game.out.extend([FourColor::Red, FourColor::Green]);
+        assert_eq!(game.result(), FourResult::Draw(DrawReason::BothTeamsOut));
+    }
+}
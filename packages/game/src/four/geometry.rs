@@ -0,0 +1,227 @@
+//! Connection tables for the four-player board.
+//!
+//! Each arm has the layout of one half of the two-player board, seen from its
+//! owner with row 0 at the back:
+//!
+//! ```text
+//! 5  R R R R R   front row, facing the centre
+//! 4  R C X C R
+//! 3  R X C X R
+//! 2  R C X C R
+//! 1  R R R R R
+//! 0  X H X H X   headquarters row
+//! ```
+//!
+//! Columns 1, 3 and 5 of each front row lead into the nine railroad squares
+//! of the centre. The side columns carry straight on through the centre into
+//! the opposite arm, and the middle column crosses the centre to the opposite
+//! front row. Curved railroads join the front corners of neighbouring arms,
+//! so column 5 of one arm continues into column 1 of the next arm in turn
+//! order.
+
+use super::{FourColor, FourSquare, ARM_SQUARES, NUM_FOUR_SQUARES};
+use crate::board::SquareType;
+use std::sync::OnceLock;
+
+const ARM_LAYOUT: [[SquareType; 5]; 6] = {
+    use SquareType::*;
+    [
+        [Empty, HQ, Empty, HQ, Empty],
+        [Railroad, Railroad, Railroad, Railroad, Railroad],
+        [Railroad, Camp, Empty, Camp, Railroad],
+        [Railroad, Empty, Camp, Empty, Railroad],
+        [Railroad, Camp, Empty, Camp, Railroad],
+        [Railroad, Railroad, Railroad, Railroad, Railroad],
+    ]
+};
+
+/// The square on `color`'s arm at `row` (0 at the back) and `col` (0 on the
+/// owner's left).
+pub const fn arm_square(color: FourColor, row: u8, col: u8) -> FourSquare {
+    color as u8 * ARM_SQUARES + row * 5 + col
+}
+
+/// The centre square at `x` (0 on Red's left) and `y` (0 nearest Red).
+pub const fn centre_square(x: u8, y: u8) -> FourSquare {
+    4 * ARM_SQUARES + y * 3 + x
+}
+
+/// The owner of the arm `sq` lies on, or `None` for the centre.
+pub const fn arm_owner(sq: FourSquare) -> Option<FourColor> {
+    match sq / ARM_SQUARES {
+        0 => Some(FourColor::Red),
+        1 => Some(FourColor::Yellow),
+        2 => Some(FourColor::Green),
+        3 => Some(FourColor::Blue),
+        _ => None,
+    }
+}
+
+/// The centre square in front of `color`'s front row column `col` (0, 2 or 4).
+fn centre_in_front(color: FourColor, col: u8) -> FourSquare {
+    let k = col / 2;
+    match color {
+        FourColor::Red => centre_square(k, 0),
+        FourColor::Yellow => centre_square(2, k),
+        FourColor::Green => centre_square(2 - k, 2),
+        FourColor::Blue => centre_square(0, 2 - k),
+    }
+}
+
+/// Precomputed tables for the four-player board.
+pub struct FourGeometry {
+    pub square_types: Vec<SquareType>,
+    /// Squares one road step away, including diagonals into and out of camps
+    /// and every railroad link.
+    pub adjacent: Vec<Vec<FourSquare>>,
+    /// Railroad links, used by engineers.
+    pub rail_links: Vec<Vec<FourSquare>>,
+    /// Straight railroad lines that other pieces may travel along.
+    pub rail_lines: Vec<Vec<FourSquare>>,
+}
+
+impl FourGeometry {
+    pub fn is_rail(&self, sq: FourSquare) -> bool {
+        !self.rail_links[sq as usize].is_empty()
+    }
+}
+
+/// The shared geometry tables.
+pub fn geometry() -> &'static FourGeometry {
+    static GEOMETRY: OnceLock<FourGeometry> = OnceLock::new();
+    GEOMETRY.get_or_init(build)
+}
+
+fn build() -> FourGeometry {
+    let mut square_types = vec![SquareType::Railroad; NUM_FOUR_SQUARES];
+    let mut adjacent = vec![Vec::new(); NUM_FOUR_SQUARES];
+    let link = |table: &mut Vec<Vec<FourSquare>>, a: FourSquare, b: FourSquare| {
+        if !table[a as usize].contains(&b) {
+            table[a as usize].push(b);
+            table[b as usize].push(a);
+        }
+    };
+
+    for color in FourColor::ALL {
+        for row in 0..6u8 {
+            for col in 0..5u8 {
+                let sq = arm_square(color, row, col);
+                let ty = ARM_LAYOUT[row as usize][col as usize];
+                square_types[sq as usize] = ty;
+                if row < 5 {
+                    link(&mut adjacent, sq, arm_square(color, row + 1, col));
+                }
+                if col < 4 {
+                    link(&mut adjacent, sq, arm_square(color, row, col + 1));
+                }
+                if ty == SquareType::Camp {
+                    for (dr, dc) in [(-1, -1), (-1, 1), (1, -1), (1, 1)] {
+                        let to = arm_square(color, (row as i8 + dr) as u8, (col as i8 + dc) as u8);
+                        link(&mut adjacent, sq, to);
+                    }
+                }
+            }
+        }
+    }
+
+    let mut rail_lines: Vec<Vec<FourSquare>> = Vec::new();
+    for color in FourColor::ALL {
+        // Back and front railroad rows
+        for row in [1, 5] {
+            rail_lines.push((0..5).map(|col| arm_square(color, row, col)).collect());
+        }
+        // Column 5 curving into the next arm's column 1
+        let next = color.next();
+        let mut arc: Vec<_> = (1..=5).map(|row| arm_square(color, row, 4)).collect();
+        arc.extend((1..=5).rev().map(|row| arm_square(next, row, 0)));
+        rail_lines.push(arc);
+    }
+    // Straight through the centre into the opposite arm
+    for color in [FourColor::Red, FourColor::Yellow] {
+        let opposite = color.next().next();
+        for col in [0, 2, 4] {
+            let mut line: Vec<_> = if col == 2 {
+                vec![arm_square(color, 5, col)]
+            } else {
+                (1..=5).map(|row| arm_square(color, row, col)).collect()
+            };
+            let start = centre_in_front(color, col);
+            let end = centre_in_front(opposite, 4 - col);
+            let step = (end as i32 - start as i32) / 2;
+            line.extend((0..3).map(|i| (start as i32 + i * step) as FourSquare));
+            if col == 2 {
+                line.push(arm_square(opposite, 5, 2));
+            } else {
+                line.extend((1..=5).rev().map(|row| arm_square(opposite, row, 4 - col)));
+            }
+            rail_lines.push(line);
+        }
+    }
+
+    let mut rail_links = vec![Vec::new(); NUM_FOUR_SQUARES];
+    for line in &rail_lines {
+        for pair in line.windows(2) {
+            link(&mut rail_links, pair[0], pair[1]);
+            link(&mut adjacent, pair[0], pair[1]);
+        }
+    }
+
+    FourGeometry {
+        square_types,
+        adjacent,
+        rail_links,
+        rail_lines,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_board_shape() {
+        let geo = geometry();
+        let count = |ty| geo.square_types.iter().filter(|&&t| t == ty).count();
+        assert_eq!(count(SquareType::HQ), 8);
+        assert_eq!(count(SquareType::Camp), 20);
+        assert_eq!(count(SquareType::Railroad), 4 * 16 + 9);
+
+        for sq in 0..NUM_FOUR_SQUARES as FourSquare {
+            assert_eq!(
+                geo.is_rail(sq),
+                geo.square_types[sq as usize] == SquareType::Railroad
+            );
+            for &to in &geo.adjacent[sq as usize] {
+                assert!(geo.adjacent[to as usize].contains(&sq));
+            }
+        }
+        // The centre is a 3x3 grid of railroad squares
+        assert_eq!(geo.rail_links[centre_square(1, 1) as usize].len(), 4);
+        assert_eq!(geo.rail_links[centre_square(0, 0) as usize].len(), 4);
+    }
+
+    #[test]
+    fn test_arm_connections() {
+        let geo = geometry();
+        let red_front_corner = arm_square(FourColor::Red, 5, 4);
+        let yellow_front_corner = arm_square(FourColor::Yellow, 5, 0);
+        assert!(geo.rail_links[red_front_corner as usize].contains(&yellow_front_corner));
+        assert!(geo.rail_links[red_front_corner as usize].contains(&centre_square(2, 0)));
+        assert!(geo.rail_links[arm_square(FourColor::Green, 5, 2) as usize]
+            .contains(&centre_square(1, 2)));
+
+        // Red's column 1 runs straight through the centre into Green's column 5
+        let line = geo
+            .rail_lines
+            .iter()
+            .find(|l| l.contains(&centre_square(0, 0)) && l[0] == arm_square(FourColor::Red, 1, 0))
+            .unwrap();
+        assert_eq!(line.len(), 13);
+        assert_eq!(line[5], centre_square(0, 0));
+        assert_eq!(line[12], arm_square(FourColor::Green, 1, 4));
+
+        // Column 2 of the front row has no way into the centre
+        let f2 = arm_square(FourColor::Blue, 5, 1);
+        assert_eq!(geo.adjacent[f2 as usize].len(), 3);
+    }
+}
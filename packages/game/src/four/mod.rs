@@ -0,0 +1,82 @@
+//! The four-player game (四国军棋).
+//!
+//! Four arms, each laid out like one side of the two-player board, surround a
+//! centre of nine railroad squares. Players sitting opposite each other are
+//! partners. Turns go round the table in [`FourColor`] order, skipping players
+//! who are out. A player is out once their flag is captured or they can no
+//! longer move, and their remaining pieces are taken off the board. The
+//! partnership with a player still in the game wins.
+//!
+//! The two-player game in the rest of the crate is unaffected; piece types,
+//! combat and [`crate::rules::Rules`] are shared between the two.
+
+pub mod game;
+pub mod geometry;
+
+use serde::{Deserialize, Serialize};
+
+/// Index of a square on the four-player board: the four arms of 30 squares
+/// in [`FourColor`] order, followed by the 9 centre squares.
+pub type FourSquare = u8;
+
+pub const ARM_SQUARES: u8 = 30;
+pub const NUM_FOUR_SQUARES: usize = 4 * ARM_SQUARES as usize + 9;
+
+/// The four players, in turn order going round the table. Red sits at the
+/// bottom and Green opposite.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FourColor {
+    Red,
+    Yellow,
+    Green,
+    Blue,
+}
+
+impl FourColor {
+    pub const ALL: [FourColor; 4] = [
+        FourColor::Red,
+        FourColor::Yellow,
+        FourColor::Green,
+        FourColor::Blue,
+    ];
+
+    /// The next player in turn order.
+    pub fn next(&self) -> Self {
+        FourColor::ALL[(*self as usize + 1) % 4]
+    }
+
+    /// The player sitting opposite.
+    pub fn partner(&self) -> Self {
+        FourColor::ALL[(*self as usize + 2) % 4]
+    }
+
+    pub fn team(&self) -> Team {
+        match self {
+            FourColor::Red | FourColor::Green => Team::RedGreen,
+            FourColor::Yellow | FourColor::Blue => Team::YellowBlue,
+        }
+    }
+}
+
+/// A partnership of two players sitting opposite each other.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Team {
+    RedGreen,
+    YellowBlue,
+}
+
+impl Team {
+    pub fn other(&self) -> Self {
+        match self {
+            Team::RedGreen => Team::YellowBlue,
+            Team::YellowBlue => Team::RedGreen,
+        }
+    }
+
+    pub fn members(&self) -> [FourColor; 2] {
+        match self {
+            Team::RedGreen => [FourColor::Red, FourColor::Green],
+            Team::YellowBlue => [FourColor::Yellow, FourColor::Blue],
+        }
+    }
+}
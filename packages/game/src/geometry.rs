@@ -0,0 +1,260 @@
+//! Precomputed connection tables for the board, derived from
+//! [`SQUARE_TO_SQUARETYPE`].
+//!
+//! Roads join every pair of orthogonal neighbours except through mountains,
+//! and diagonal roads lead into and out of camps. Railroads are the subset of
+//! orthogonal links between railroad and frontline squares: the back and front
+//! rows of each side, the two side columns which bend into the back rows at
+//! the corners (L1, L5, B1, B5), and the three frontline crossings at G1, G3
+//! and G5. The mountains at G2 and G4 have no links at all.
+
+use crate::board::{
+    down, down_left, down_right, left, right, sq, up, up_left, up_right, SquareIndex, SquareType,
+    NUM_SQUARES, SQUARE_TO_SQUARETYPE,
+};
+
+/// The squares linked to one square, in up, down, left, right order followed
+/// by up-left, up-right, down-left, down-right.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Links {
+    len: u8,
+    squares: [SquareIndex; 8],
+}
+
+impl Links {
+    const EMPTY: Links = Links {
+        len: 0,
+        squares: [0; 8],
+    };
+
+    const fn push(mut self, to: Option<SquareIndex>) -> Self {
+        if let Some(to) = to {
+            self.squares[self.len as usize] = to;
+            self.len += 1;
+        }
+        self
+    }
+
+    pub fn as_slice(&self) -> &[SquareIndex] {
+        &self.squares[..self.len as usize]
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = SquareIndex> + '_ {
+        self.as_slice().iter().copied()
+    }
+
+    pub fn contains(&self, sq: SquareIndex) -> bool {
+        self.as_slice().contains(&sq)
+    }
+
+    pub fn len(&self) -> usize {
+        self.len as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+const fn is_mountain(sq: SquareIndex) -> bool {
+    matches!(SQUARE_TO_SQUARETYPE[sq as usize], SquareType::Mountain)
+}
+
+const fn is_camp(sq: SquareIndex) -> bool {
+    matches!(SQUARE_TO_SQUARETYPE[sq as usize], SquareType::Camp)
+}
+
+/// Railroads and frontline squares together form the railroad network.
+pub const fn is_rail(sq: SquareIndex) -> bool {
+    matches!(
+        SQUARE_TO_SQUARETYPE[sq as usize],
+        SquareType::Railroad | SquareType::Frontline
+    )
+}
+
+const fn road(from: SquareIndex, to: Option<SquareIndex>) -> Option<SquareIndex> {
+    match to {
+        Some(to) if !is_mountain(from) && !is_mountain(to) => Some(to),
+        _ => None,
+    }
+}
+
+const fn camp_road(from: SquareIndex, to: Option<SquareIndex>) -> Option<SquareIndex> {
+    match road(from, to) {
+        Some(to) if is_camp(from) || is_camp(to) => Some(to),
+        _ => None,
+    }
+}
+
+const fn rail(from: SquareIndex, to: Option<SquareIndex>) -> Option<SquareIndex> {
+    match to {
+        Some(to) if is_rail(from) && is_rail(to) => Some(to),
+        _ => None,
+    }
+}
+
+const fn build_orthogonal() -> [Links; NUM_SQUARES] {
+    let mut table = [Links::EMPTY; NUM_SQUARES];
+    let mut i = 0;
+    while i < NUM_SQUARES {
+        let s = i as SquareIndex;
+        table[i] = Links::EMPTY
+            .push(road(s, up(s)))
+            .push(road(s, down(s)))
+            .push(road(s, left(s)))
+            .push(road(s, right(s)));
+        i += 1;
+    }
+    table
+}
+
+const fn build_diagonal() -> [Links; NUM_SQUARES] {
+    let mut table = [Links::EMPTY; NUM_SQUARES];
+    let mut i = 0;
+    while i < NUM_SQUARES {
+        let s = i as SquareIndex;
+        table[i] = Links::EMPTY
+            .push(camp_road(s, up_left(s)))
+            .push(camp_road(s, up_right(s)))
+            .push(camp_road(s, down_left(s)))
+            .push(camp_road(s, down_right(s)));
+        i += 1;
+    }
+    table
+}
+
+const fn build_adjacent() -> [Links; NUM_SQUARES] {
+    let mut table = [Links::EMPTY; NUM_SQUARES];
+    let mut i = 0;
+    while i < NUM_SQUARES {
+        let s = i as SquareIndex;
+        table[i] = Links::EMPTY
+            .push(road(s, up(s)))
+            .push(road(s, down(s)))
+            .push(road(s, left(s)))
+            .push(road(s, right(s)))
+            .push(camp_road(s, up_left(s)))
+            .push(camp_road(s, up_right(s)))
+            .push(camp_road(s, down_left(s)))
+            .push(camp_road(s, down_right(s)));
+        i += 1;
+    }
+    table
+}
+
+const fn build_rail() -> [Links; NUM_SQUARES] {
+    let mut table = [Links::EMPTY; NUM_SQUARES];
+    let mut i = 0;
+    while i < NUM_SQUARES {
+        let s = i as SquareIndex;
+        table[i] = Links::EMPTY
+            .push(rail(s, up(s)))
+            .push(rail(s, down(s)))
+            .push(rail(s, left(s)))
+            .push(rail(s, right(s)));
+        i += 1;
+    }
+    table
+}
+
+/// Orthogonal road links of each square.
+pub const ORTHOGONAL_LINKS: [Links; NUM_SQUARES] = build_orthogonal();
+
+/// Diagonal road links of each square; these always start or end in a camp.
+pub const DIAGONAL_LINKS: [Links; NUM_SQUARES] = build_diagonal();
+
+/// All road links of each square, orthogonal first.
+pub const ADJACENT: [Links; NUM_SQUARES] = build_adjacent();
+
+/// Railroad links of each square.
+pub const RAIL_LINKS: [Links; NUM_SQUARES] = build_rail();
+
+/// The straight railroad lines. Pieces other than engineers move along a
+/// single line; engineers may also switch lines where two of them meet.
+pub const RAIL_LINES: [&[SquareIndex]; 7] = [
+    &[sq::L1, sq::L2, sq::L3, sq::L4, sq::L5],
+    &[sq::H1, sq::H2, sq::H3, sq::H4, sq::H5],
+    &[sq::F1, sq::F2, sq::F3, sq::F4, sq::F5],
+    &[sq::B1, sq::B2, sq::B3, sq::B4, sq::B5],
+    &[
+        sq::L1,
+        sq::K1,
+        sq::J1,
+        sq::I1,
+        sq::H1,
+        sq::G1,
+        sq::F1,
+        sq::E1,
+        sq::D1,
+        sq::C1,
+        sq::B1,
+    ],
+    &[
+        sq::L5,
+        sq::K5,
+        sq::J5,
+        sq::I5,
+        sq::H5,
+        sq::G5,
+        sq::F5,
+        sq::E5,
+        sq::D5,
+        sq::C5,
+        sq::B5,
+    ],
+    &[sq::H3, sq::G3, sq::F3],
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_links_are_symmetric() {
+        for table in [&ORTHOGONAL_LINKS, &DIAGONAL_LINKS, &ADJACENT, &RAIL_LINKS] {
+            for from in 0..NUM_SQUARES as SquareIndex {
+                for to in table[from as usize].iter() {
+                    assert!(table[to as usize].contains(from), "{from} -> {to}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_rail_lines_match_rail_links() {
+        let mut count = 0;
+        for line in RAIL_LINES {
+            for pair in line.windows(2) {
+                assert!(RAIL_LINKS[pair[0] as usize].contains(pair[1]));
+                count += 2;
+            }
+        }
+        let total: usize = RAIL_LINKS.iter().map(|l| l.len()).sum();
+        assert_eq!(total, count);
+    }
+
+    #[test]
+    fn test_special_squares() {
+        // Mountains are cut off entirely
+        for m in [sq::G2, sq::G4] {
+            assert!(ADJACENT[m as usize].is_empty());
+        }
+        assert!(!ADJACENT[sq::H2 as usize].contains(sq::G2));
+        assert!(!ADJACENT[sq::F2 as usize].contains(sq::G2));
+
+        // Frontline crossings only connect the two halves
+        assert_eq!(RAIL_LINKS[sq::G3 as usize].as_slice(), &[sq::H3, sq::F3]);
+        assert_eq!(ADJACENT[sq::G1 as usize].as_slice(), &[sq::H1, sq::F1]);
+
+        // Corners bend the side columns into the back rows
+        assert_eq!(RAIL_LINKS[sq::L1 as usize].as_slice(), &[sq::K1, sq::L2]);
+        assert_eq!(RAIL_LINKS[sq::B5 as usize].as_slice(), &[sq::C5, sq::B4]);
+
+        // Camps have diagonal roads in all four directions
+        assert_eq!(
+            DIAGONAL_LINKS[sq::D3 as usize].as_slice(),
+            &[sq::E2, sq::E4, sq::C2, sq::C4]
+        );
+        assert!(DIAGONAL_LINKS[sq::D2 as usize].is_empty());
+    }
+}
@@ -0,0 +1,220 @@
+//! Taking moves back.
+//!
+//! Every move played through [`GameState::make_move_unchecked`] is pushed onto
+//! `GameState::history` together with the little state the move destroys, so
+//! [`GameState::unmake_move`] can restore the position exactly without keeping
+//! copies of the board. Search uses make/unmake pairs directly; clients use
+//! [`GameState::undo`] and [`GameState::redo`].
+
+use crate::board::{Color, GameState, Piece, PieceType};
+use crate::combat::{CombatResult, MoveOutcome};
+use crate::movegen::Move;
+use crate::zobrist;
+use serde::{Deserialize, Serialize};
+
+/// A move that was played, and the state needed to take it back.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MoveRecord {
+    pub mv: Move,
+    pub outcome: MoveOutcome,
+    /// The Zobrist key of the position before the move.
+    pub key: u64,
+    pub(crate) from_revealed: bool,
+    pub(crate) to_piece: Option<Piece>,
+    pub(crate) to_revealed: bool,
+    pub(crate) plies_since_capture: u32,
+    pub(crate) draw_offer: Option<Color>,
+    /// Whether the move revealed each color's flag.
+    pub(crate) flags_revealed: [bool; 2],
+}
+
+impl GameState {
+    /// The most recently played move, if any.
+    pub fn last_move(&self) -> Option<&MoveRecord> {
+        self.history.last()
+    }
+
+    /// Takes back the last move in `history`, restoring the board, captured
+    /// pieces, revealed squares, side to move and counters. Returns the record
+    /// of the move, or `None` if no move has been played.
+    pub fn unmake_move(&mut self) -> Option<MoveRecord> {
+        let record = self.history.pop()?;
+        let (from, to) = (record.mv.from as usize, record.mv.to as usize);
+
+        for color in [Color::Red, Color::Black] {
+            if record.flags_revealed[color as usize] {
+                if let Some(sq) = self.flag_square(color) {
+                    self.revealed[sq as usize] = false;
+                }
+            }
+        }
+
+        let attacker = match record.outcome {
+            MoveOutcome::Moved => self.board[to].expect("moved piece is missing"),
+            MoveOutcome::Attack { attacker, .. } | MoveOutcome::FlagCaptured { attacker, .. } => {
+                attacker
+            }
+        };
+        let removed = match record.outcome {
+            MoveOutcome::Moved => 0,
+            MoveOutcome::Attack {
+                result: CombatResult::BothRemoved,
+                ..
+            } => 2,
+            MoveOutcome::FlagCaptured { attacker, .. } if attacker.ty == PieceType::Bomb => 2,
+            _ => 1,
+        };
+        self.captured.truncate(self.captured.len() - removed);
+
+        let after = self.board[to];
+        let key_before = zobrist::square_key(record.mv.to, after);
+        self.board[from] = Some(attacker);
+        self.revealed[from] = record.from_revealed;
+        self.board[to] = record.to_piece;
+        self.revealed[to] = record.to_revealed;
+
+        self.zobrist ^= key_before
+            ^ zobrist::square_key(record.mv.from, Some(attacker))
+            ^ zobrist::square_key(record.mv.to, record.to_piece)
+            ^ zobrist::side_key();
+        self.occupancy.toggle(record.mv.to, after);
+        self.occupancy.toggle(record.mv.from, Some(attacker));
+        self.occupancy.toggle(record.mv.to, record.to_piece);
+
+        self.turn = self.turn.other();
+        self.ply -= 1;
+        self.plies_since_capture = record.plies_since_capture;
+        self.draw_offer = record.draw_offer;
+        Some(record)
+    }
+
+    /// Takes back the last move so that it can be replayed with
+    /// [`GameState::redo`]. Returns the move, or `None` if there is nothing to
+    /// undo.
+    pub fn undo(&mut self) -> Option<Move> {
+        let mv = self.unmake_move()?.mv;
+        self.redo_stack.push(mv);
+        Some(mv)
+    }
+
+    /// Replays the last move taken back with [`GameState::undo`].
+    pub fn redo(&mut self) -> Option<MoveOutcome> {
+        let mv = self.redo_stack.pop()?;
+        Some(self.make_move_unchecked(mv))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::sq;
+
+    fn assert_same(a: &GameState, b: &GameState) {
+        assert_eq!(a.board, b.board);
+        assert_eq!(a.revealed, b.revealed);
+        assert_eq!(a.captured, b.captured);
+        assert_eq!(a.turn, b.turn);
+        assert_eq!(a.ply, b.ply);
+        assert_eq!(a.plies_since_capture, b.plies_since_capture);
+        assert_eq!(a.draw_offer, b.draw_offer);
+        assert_eq!(a.history, b.history);
+    }
+
+    #[test]
+    fn test_unmake_restores_random_games() {
+        for seed in 0..20 {
+            let mut state = GameState::random_start(seed);
+            let mut snapshots = Vec::new();
+            for i in 0..200 {
+                let moves = state.legal_moves();
+                if moves.is_empty() || state.result().is_over() {
+                    break;
+                }
+                if i % 7 == 3 {
+                    state.offer_draw(state.turn);
+                }
+                snapshots.push(state.clone());
+                let mv = moves[(seed as usize * 31 + i * 17) % moves.len()];
+                state.make_move_unchecked(mv);
+            }
+            while let Some(expected) = snapshots.pop() {
+                state.unmake_move().unwrap();
+                assert_same(&state, &expected);
+            }
+            assert!(state.unmake_move().is_none());
+        }
+    }
+
+    #[test]
+    fn test_unmake_hides_revealed_flags() {
+        let mut state = GameState::new();
+        for (square, ty, color) in [
+            (sq::F1, PieceType::Overall, Color::Red),
+            (sq::H1, PieceType::Overall, Color::Black),
+            (sq::A2, PieceType::Flag, Color::Red),
+            (sq::M2, PieceType::Flag, Color::Black),
+        ] {
+            state.set_piece(square, Some(Piece { ty, color }));
+        }
+        let before = state.clone();
+        state.make_move(Move::new(sq::F1, sq::H1)).unwrap();
+        assert!(state.flag_revealed(Color::Red));
+        assert!(state.flag_revealed(Color::Black));
+        assert_eq!(state.captured.len(), 2);
+
+        state.unmake_move();
+        assert_same(&state, &before);
+    }
+
+    #[test]
+    fn test_unmake_bomb_on_flag() {
+        let mut state = GameState::new();
+        state.set_piece(
+            sq::L2,
+            Some(Piece {
+                ty: PieceType::Bomb,
+                color: Color::Red,
+            }),
+        );
+        state.set_piece(
+            sq::M2,
+            Some(Piece {
+                ty: PieceType::Flag,
+                color: Color::Black,
+            }),
+        );
+        let before = state.clone();
+        state.make_move(Move::new(sq::L2, sq::M2)).unwrap();
+        assert_eq!(state.captured.len(), 2);
+        state.unmake_move();
+        assert_same(&state, &before);
+    }
+
+    #[test]
+    fn test_undo_redo() {
+        let mut state = GameState::random_start(3);
+        let start = state.clone();
+        let first = state.legal_moves()[0];
+        state.make_move(first).unwrap();
+        let second = state.legal_moves()[0];
+        state.make_move(second).unwrap();
+        let after = state.clone();
+
+        assert_eq!(state.undo(), Some(second));
+        assert_eq!(state.undo(), Some(first));
+        assert_eq!(state.undo(), None);
+        assert_eq!(state.board, start.board);
+        assert_eq!(state.redo_stack, vec![second, first]);
+
+        state.redo().unwrap();
+        state.redo().unwrap();
+        assert!(state.redo().is_none());
+        assert_eq!(state.board, after.board);
+        assert_eq!(state.last_move().unwrap().mv, second);
+
+        // Playing a new move forgets the undone ones
+        state.undo();
+        state.make_move(state.legal_moves()[1]).unwrap();
+        assert!(state.redo_stack.is_empty());
+    }
+}
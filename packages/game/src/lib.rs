@@ -1,2 +1,19 @@
+pub mod bitboard;
 pub mod board;
+pub mod combat;
+pub mod four;
+pub mod geometry;
+pub mod history;
+pub mod movegen;
+pub mod notation;
+pub mod perft;
 pub mod prng;
+pub mod rail;
+pub mod repetition;
+pub mod result;
+pub mod rules;
+pub mod setup;
+pub mod square;
+pub mod symmetry;
+pub mod view;
+pub mod zobrist;
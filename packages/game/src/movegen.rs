@@ -0,0 +1,241 @@
+use crate::bitboard::{Bitboard, CAMPS, FRONTLINE, HQS, LANDABLE};
+use crate::board::{GameState, Piece, PieceType, SquareIndex};
+use crate::geometry::{is_rail, ADJACENT, ORTHOGONAL_LINKS, RAIL_LINES};
+use serde::{Deserialize, Serialize};
+
+/// A move of the piece on `from` to `to`.
+/// Moving onto an enemy piece is an attack.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Move {
+    pub from: SquareIndex,
+    pub to: SquareIndex,
+}
+
+impl Move {
+    pub fn new(from: SquareIndex, to: SquareIndex) -> Self {
+        Self { from, to }
+    }
+}
+
+/// Pieces can never stop on the frontline, so a step onto it carries on to
+/// the square straight beyond.
+fn step_over_frontline(from: SquareIndex, to: SquareIndex) -> Option<SquareIndex> {
+    if !FRONTLINE.contains(to) {
+        return Some(to);
+    }
+    let beyond = (2 * to as i32 - from as i32) as SquareIndex;
+    ORTHOGONAL_LINKS[to as usize]
+        .contains(beyond)
+        .then_some(beyond)
+}
+
+impl GameState {
+    /// Returns every legal move for the side to move.
+    pub fn legal_moves(&self) -> Vec<Move> {
+        let mut moves = Vec::new();
+        for from in self.occupancy.color(self.turn) {
+            self.gen_piece_moves(from, &mut moves);
+        }
+        moves
+    }
+
+    /// Returns the legal moves of the piece on `from`, regardless of whose turn it is.
+    pub fn moves_from(&self, from: SquareIndex) -> Vec<Move> {
+        let mut moves = Vec::new();
+        self.gen_piece_moves(from, &mut moves);
+        moves
+    }
+
+    /// Whether `piece` may end a move on `to`: the square must be empty or hold
+    /// an enemy piece outside a camp. Frontline squares can never be landed on,
+    /// and bombs cannot attack the flag if `rules` bar them from it.
+    pub(crate) fn can_land(&self, piece: Piece, to: SquareIndex) -> bool {
+        let blocked = if CAMPS.contains(to) {
+            self.occupancy.occupied()
+        } else {
+            self.occupancy.color(piece.color)
+        };
+        if piece.ty == PieceType::Bomb && !self.rules.bomb_can_attack_flag {
+            let flag = Piece {
+                ty: PieceType::Flag,
+                color: piece.color.other(),
+            };
+            if self.board[to as usize] == Some(flag) {
+                return false;
+            }
+        }
+        LANDABLE.contains(to) && !blocked.contains(to)
+    }
+
+    fn gen_piece_moves(&self, from: SquareIndex, moves: &mut Vec<Move>) {
+        let piece = match self.board[from as usize] {
+            Some(p) => p,
+            None => return,
+        };
+        if matches!(piece.ty, PieceType::Landmine | PieceType::Flag) || HQS.contains(from) {
+            return;
+        }
+
+        let mut seen = Bitboard::EMPTY;
+        let mut push = |to: SquareIndex, moves: &mut Vec<Move>| {
+            if !seen.contains(to) {
+                seen.insert(to);
+                moves.push(Move::new(from, to));
+            }
+        };
+
+        // One step along a road, including diagonals into and out of camps
+        for to in ADJACENT[from as usize].iter() {
+            if let Some(to) = step_over_frontline(from, to) {
+                if self.can_land(piece, to) {
+                    push(to, moves);
+                }
+            }
+        }
+
+        if !is_rail(from) {
+            return;
+        }
+
+        if piece.ty == PieceType::Engineer {
+            // Engineers may travel anywhere along connected railroad, turning corners
+            for to in self.rail_reach(from).destinations() {
+                push(to, moves);
+            }
+        } else {
+            // Other pieces move any distance in a straight line along the railroad
+            for line in RAIL_LINES {
+                let pos = match line.iter().position(|&s| s == from) {
+                    Some(pos) => pos,
+                    None => continue,
+                };
+                for dir in [1, -1] {
+                    let mut i = pos as isize + dir;
+                    while let Some(&to) = line.get(i as usize) {
+                        if self.can_land(piece, to) {
+                            push(to, moves);
+                        }
+                        if self.occupancy.occupied().contains(to) {
+                            break;
+                        }
+                        i += dir;
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::{sq, Color};
+
+    fn targets(state: &GameState, from: SquareIndex) -> Vec<SquareIndex> {
+        let mut to: Vec<_> = state.moves_from(from).iter().map(|m| m.to).collect();
+        to.sort();
+        to
+    }
+
+    #[test]
+    fn test_step_moves_and_camp_diagonals() {
+        let state = GameState::from_pieces(&[
+            (sq::D3, PieceType::Company, Color::Red),
+            (sq::A3, PieceType::Company, Color::Red),
+        ]);
+        assert_eq!(
+            targets(&state, sq::D3),
+            vec![
+                sq::E2,
+                sq::E3,
+                sq::E4,
+                sq::D2,
+                sq::D4,
+                sq::C2,
+                sq::C3,
+                sq::C4
+            ]
+        );
+
+        // A3 is not a camp, so no diagonals
+        assert_eq!(targets(&state, sq::A3), vec![sq::B3, sq::A2, sq::A4]);
+    }
+
+    #[test]
+    fn test_straight_railroad_moves() {
+        let state = GameState::from_pieces(&[
+            (sq::B1, PieceType::Army, Color::Red),
+            (sq::D1, PieceType::Platoon, Color::Red),
+            (sq::B4, PieceType::Platoon, Color::Black),
+        ]);
+        assert_eq!(
+            targets(&state, sq::B1),
+            vec![sq::C1, sq::C2, sq::B2, sq::B3, sq::B4, sq::A1]
+        );
+    }
+
+    #[test]
+    fn test_frontline_is_crossed_but_not_landed_on() {
+        let state = GameState::from_pieces(&[
+            (sq::F3, PieceType::Platoon, Color::Red),
+            (sq::F2, PieceType::Platoon, Color::Red),
+        ]);
+        let to = targets(&state, sq::F3);
+        assert!(to.contains(&sq::H3));
+        assert!(!to.contains(&sq::G3));
+        assert!(!to.contains(&sq::I3));
+
+        // F2 faces a mountain
+        let to = targets(&state, sq::F2);
+        assert!(!to.contains(&sq::G2));
+        assert!(!to.contains(&sq::H2));
+    }
+
+    #[test]
+    fn test_engineer_turns_corners() {
+        let engineer = (sq::B3, PieceType::Engineer, Color::Red);
+        let state = GameState::from_pieces(&[engineer]);
+        let to = targets(&state, sq::B3);
+        assert!(to.contains(&sq::L5));
+        assert!(to.contains(&sq::H2));
+        assert!(!to.contains(&sq::G1));
+
+        let blocked = GameState::from_pieces(&[
+            engineer,
+            (sq::F4, PieceType::Engineer, Color::Black),
+            (sq::F1, PieceType::Platoon, Color::Red),
+        ]);
+        let to = targets(&blocked, sq::B3);
+        assert!(to.contains(&sq::F4));
+        assert!(!to.contains(&sq::F1));
+        // F2 is still reachable the long way round through H3 and G3
+        assert!(to.contains(&sq::F2));
+    }
+
+    #[test]
+    fn test_immobile_pieces_and_camps() {
+        let state = GameState::from_pieces(&[
+            (sq::A2, PieceType::Army, Color::Red),
+            (sq::B5, PieceType::Landmine, Color::Red),
+            (sq::A4, PieceType::Flag, Color::Red),
+            (sq::E1, PieceType::Army, Color::Red),
+            (sq::E2, PieceType::Platoon, Color::Black),
+        ]);
+        assert!(state.moves_from(sq::A2).is_empty());
+        assert!(state.moves_from(sq::B5).is_empty());
+        assert!(state.moves_from(sq::A4).is_empty());
+
+        // Pieces in a camp cannot be attacked
+        assert!(!targets(&state, sq::E1).contains(&sq::E2));
+    }
+
+    #[test]
+    fn test_legal_moves_only_for_side_to_move() {
+        let state = GameState::random_start(42);
+        let moves = state.legal_moves();
+        assert!(!moves.is_empty());
+        for m in moves {
+            assert_eq!(state.board[m.from as usize].unwrap().color, Color::Red);
+        }
+    }
+}
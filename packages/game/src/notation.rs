@@ -0,0 +1,262 @@
+//! A compact text notation for positions, modelled on chess FEN.
+//!
+//! ```text
+//! 3f1/5/5/5/5/2E2/5/5/5/5/5/5/1F3 b 1 1
+//! ```
+//!
+//! The first field lists the 13 rows from `m` down to `a`, separated by `/`.
+//! Each row gives its 5 squares from column 1 to 5: a piece letter (uppercase
+//! for Red, lowercase for Black, see [`crate::board::PieceType`]) or a digit counting empty
+//! squares. The remaining fields are the side to move (`r` or `b`), the plies
+//! since the last attack, and the move number, which starts at 1 and goes up
+//! after each Black move. The example above has Black's flag on m4, a Red
+//! engineer on h3 and Red's flag on a2, with Black to move.
+
+use crate::board::{Color, GameState, Piece, SquareIndex, SquareType, SQUARE_TO_SQUARETYPE};
+use crate::square::Square;
+use std::fmt;
+
+const ROWS: usize = 13;
+const COLS: usize = 5;
+const ROW_NAMES: &[u8; ROWS] = b"mlkjihgfedcba";
+
+/// Reasons [`GameState::from_notation`] can reject a string.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NotationError {
+    /// Expected 4 space-separated fields.
+    FieldCount(usize),
+    /// Expected 13 rows in the board field.
+    RowCount(usize),
+    /// A row does not describe exactly 5 squares.
+    RowLength { row: char, len: usize },
+    /// A character in a row is neither a piece letter nor a digit 1-5.
+    InvalidPiece { row: char, c: char },
+    /// A piece sits on a mountain or frontline square.
+    UnplayableSquare { square: Square },
+    /// The side to move is not `r` or `b`.
+    InvalidTurn(String),
+    /// A move counter is not a valid number.
+    InvalidCounter(String),
+}
+
+impl fmt::Display for NotationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NotationError::FieldCount(n) => write!(f, "expected 4 fields, found {n}"),
+            NotationError::RowCount(n) => write!(f, "expected 13 rows, found {n}"),
+            NotationError::RowLength { row, len } => {
+                write!(f, "row {row} describes {len} squares instead of 5")
+            }
+            NotationError::InvalidPiece { row, c } => {
+                write!(f, "invalid character '{c}' in row {row}")
+            }
+            NotationError::UnplayableSquare { square } => {
+                write!(f, "piece on unplayable square {square}")
+            }
+            NotationError::InvalidTurn(s) => {
+                write!(f, "invalid side to move '{s}', expected 'r' or 'b'")
+            }
+            NotationError::InvalidCounter(s) => write!(f, "invalid move counter '{s}'"),
+        }
+    }
+}
+
+impl std::error::Error for NotationError {}
+
+impl GameState {
+    /// Writes the position in the notation described in [`crate::notation`].
+    pub fn to_notation(&self) -> String {
+        let mut out = String::new();
+        for row in 0..ROWS {
+            if row > 0 {
+                out.push('/');
+            }
+            let mut empty = 0;
+            for col in 0..COLS {
+                match self.board[row * COLS + col] {
+                    Some(p) => {
+                        if empty > 0 {
+                            out.push_str(&empty.to_string());
+                            empty = 0;
+                        }
+                        out.push(p.to_char());
+                    }
+                    None => empty += 1,
+                }
+            }
+            if empty > 0 {
+                out.push_str(&empty.to_string());
+            }
+        }
+
+        let turn = match self.turn {
+            Color::Red => 'r',
+            Color::Black => 'b',
+        };
+        format!(
+            "{out} {turn} {} {}",
+            self.plies_since_capture,
+            self.ply / 2 + 1
+        )
+    }
+
+    /// Parses a position written by [`GameState::to_notation`].
+    pub fn from_notation(s: &str) -> Result<Self, NotationError> {
+        let fields: Vec<&str> = s.split_whitespace().collect();
+        if fields.len() != 4 {
+            return Err(NotationError::FieldCount(fields.len()));
+        }
+
+        let rows: Vec<&str> = fields[0].split('/').collect();
+        if rows.len() != ROWS {
+            return Err(NotationError::RowCount(rows.len()));
+        }
+
+        let mut state = GameState::new();
+        for (r, row) in rows.iter().enumerate() {
+            let name = ROW_NAMES[r] as char;
+            let mut col = 0;
+            for c in row.chars() {
+                if let Some(n) = c.to_digit(10).filter(|n| (1..=5).contains(n)) {
+                    col += n as usize;
+                    continue;
+                }
+                let piece =
+                    Piece::from_char(c).ok_or(NotationError::InvalidPiece { row: name, c })?;
+                if col < COLS {
+                    let idx = r * COLS + col;
+                    if matches!(
+                        SQUARE_TO_SQUARETYPE[idx],
+                        SquareType::Mountain | SquareType::Frontline
+                    ) {
+                        return Err(NotationError::UnplayableSquare {
+                            square: Square::new(idx as SquareIndex).unwrap(),
+                        });
+                    }
+                    state.board[idx] = Some(piece);
+                }
+                col += 1;
+            }
+            if col != COLS {
+                return Err(NotationError::RowLength {
+                    row: name,
+                    len: col,
+                });
+            }
+        }
+
+        state.turn = match fields[1] {
+            "r" => Color::Red,
+            "b" => Color::Black,
+            other => return Err(NotationError::InvalidTurn(other.to_string())),
+        };
+
+        let counter = |s: &str| {
+            s.parse::<u32>()
+                .map_err(|_| NotationError::InvalidCounter(s.to_string()))
+        };
+        state.plies_since_capture = counter(fields[2])?;
+        // The move number must be at least 1 and its ply must fit in a u32
+        state.ply = counter(fields[3])?
+            .checked_sub(1)
+            .and_then(|n| n.checked_mul(2))
+            .and_then(|n| n.checked_add((state.turn == Color::Black) as u32))
+            .ok_or_else(|| NotationError::InvalidCounter(fields[3].to_string()))?;
+        state.refresh();
+
+        Ok(state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::{sq, PieceType};
+    use crate::movegen::Move;
+
+    #[test]
+    fn test_round_trip() {
+        for seed in [1, 2, 3, 1000] {
+            let mut state = GameState::random_start(seed);
+            let moves = state.legal_moves();
+            state.make_move(moves[0]).unwrap();
+
+            let text = state.to_notation();
+            let parsed = GameState::from_notation(&text).unwrap();
+            assert_eq!(parsed.board, state.board);
+            assert_eq!(parsed.turn, state.turn);
+            assert_eq!(parsed.ply, state.ply);
+            assert_eq!(parsed.plies_since_capture, state.plies_since_capture);
+            assert_eq!(parsed.to_notation(), text);
+        }
+    }
+
+    #[test]
+    fn test_known_position() {
+        let mut state = GameState::new();
+        state.set_piece(
+            sq::A2,
+            Some(Piece {
+                ty: PieceType::Flag,
+                color: Color::Red,
+            }),
+        );
+        state.set_piece(
+            sq::M4,
+            Some(Piece {
+                ty: PieceType::Flag,
+                color: Color::Black,
+            }),
+        );
+        state.set_piece(
+            sq::F3,
+            Some(Piece {
+                ty: PieceType::Engineer,
+                color: Color::Red,
+            }),
+        );
+        assert_eq!(state.to_notation(), "3f1/5/5/5/5/5/5/2E2/5/5/5/5/1F3 r 0 1");
+        state.make_move(Move::new(sq::F3, sq::H3)).unwrap();
+        assert_eq!(state.to_notation(), "3f1/5/5/5/5/2E2/5/5/5/5/5/5/1F3 b 1 1");
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        use NotationError::*;
+        let empty = "5/5/5/5/5/5/5/5/5/5/5/5/5";
+        let parse = |s: &str| GameState::from_notation(s).unwrap_err();
+
+        assert_eq!(parse(empty), FieldCount(1));
+        assert_eq!(parse("5/5 r 0 1"), RowCount(2));
+        assert_eq!(
+            parse("5/5/5/5/5/5/5/5/5/5/5/42/5 r 0 1"),
+            RowLength { row: 'b', len: 6 }
+        );
+        assert_eq!(
+            parse("5/5/5/5/5/5/5/2Q2/5/5/5/5/5 r 0 1"),
+            InvalidPiece { row: 'f', c: 'Q' }
+        );
+        assert_eq!(
+            parse("5/5/5/5/5/5/1E3/5/5/5/5/5/5 r 0 1"),
+            UnplayableSquare {
+                square: "g2".parse().unwrap()
+            }
+        );
+        assert_eq!(
+            parse(&format!("{empty} w 0 1")),
+            InvalidTurn("w".to_string())
+        );
+        assert_eq!(
+            parse(&format!("{empty} r x 1")),
+            InvalidCounter("x".to_string())
+        );
+        assert_eq!(
+            parse(&format!("{empty} r 0 0")),
+            InvalidCounter("0".to_string())
+        );
+        assert_eq!(
+            parse(&format!("{empty} r 0 4294967295")),
+            InvalidCounter("4294967295".to_string())
+        );
+    }
+}
@@ -0,0 +1,78 @@
+//! Move path enumeration for testing move generation.
+//!
+//! [`GameState::perft`] counts the move sequences of a given length from a
+//! position, and [`GameState::divide`] splits that count by first move.
+//! Comparing the counts with known values catches changes in what
+//! [`GameState::legal_moves`] generates, especially along the railroad where
+//! engineers turn corners. Moves are played and taken back with
+//! [`GameState::make_move_unchecked`] and [`GameState::unmake_move`].
+//!
+//! Paths stop early once [`GameState::result`] says the game is over. Moves
+//! over the repetition limits are still counted, but the limits do end a
+//! path when the side to move has no move within them, as that side has
+//! lost.
+
+use crate::board::GameState;
+use crate::movegen::Move;
+
+impl GameState {
+    /// The number of move sequences of exactly `depth` plies from this
+    /// position.
+    pub fn perft(&self, depth: u32) -> u64 {
+        self.clone().perft_inner(depth)
+    }
+
+    /// The [`GameState::perft`] count below each legal move, in the order of
+    /// [`GameState::legal_moves`].
+    pub fn divide(&self, depth: u32) -> Vec<(Move, u64)> {
+        if depth == 0 || self.result().is_over() {
+            return Vec::new();
+        }
+        let mut state = self.clone();
+        self.legal_moves()
+            .into_iter()
+            .map(|mv| {
+                state.make_move_unchecked(mv);
+                let count = state.perft_inner(depth - 1);
+                state.unmake_move();
+                (mv, count)
+            })
+            .collect()
+    }
+
+    fn perft_inner(&mut self, depth: u32) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+        if self.result().is_over() {
+            return 0;
+        }
+        let moves = self.legal_moves();
+        if depth == 1 {
+            return moves.len() as u64;
+        }
+        let mut count = 0;
+        for mv in moves {
+            self.make_move_unchecked(mv);
+            count += self.perft_inner(depth - 1);
+            self.unmake_move();
+        }
+        count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_divide_matches_perft() {
+        let state = GameState::random_start(11);
+        assert_eq!(state.perft(0), 1);
+        assert_eq!(state.perft(1), state.legal_moves().len() as u64);
+
+        let divided = state.divide(2);
+        assert_eq!(divided.len(), state.legal_moves().len());
+        assert_eq!(divided.iter().map(|&(_, n)| n).sum::<u64>(), state.perft(2));
+    }
+}
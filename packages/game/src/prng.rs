@@ -16,6 +16,10 @@ impl PseudoRng {
         (x as u32).wrapping_mul(0x9E3779B1)
     }
 
+    pub fn next_u64(&mut self) -> u64 {
+        ((self.next_u32() as u64) << 32) | self.next_u32() as u64
+    }
+
     pub fn gen_range(&mut self, range: std::ops::Range<usize>) -> usize {
         let len = range.end - range.start;
         if len == 0 {
@@ -0,0 +1,188 @@
+//! Engineer movement along the railroad network.
+
+use crate::bitboard::Bitboard;
+use crate::board::{GameState, SquareIndex, NUM_SQUARES};
+use crate::geometry::{is_rail, RAIL_LINKS};
+
+const NO_PARENT: SquareIndex = SquareIndex::MAX;
+
+/// The squares an engineer can reach by rail from one square, together with
+/// a shortest path to each of them.
+#[derive(Debug, Clone)]
+pub struct RailReach {
+    from: SquareIndex,
+    /// The squares the engineer can end its move on.
+    destinations: Bitboard,
+    /// The square each visited square was first reached from.
+    parent: [SquareIndex; NUM_SQUARES],
+}
+
+impl RailReach {
+    /// The square the engineer starts from.
+    pub fn from(&self) -> SquareIndex {
+        self.from
+    }
+
+    /// Whether the engineer can end its move on `to`.
+    pub fn contains(&self, to: SquareIndex) -> bool {
+        self.destinations.contains(to)
+    }
+
+    /// Every square the engineer can end its move on, in index order.
+    pub fn destinations(&self) -> impl Iterator<Item = SquareIndex> + '_ {
+        self.destinations.squares()
+    }
+
+    /// A shortest railroad path from the starting square to `to`, both ends
+    /// included. Frontline squares the engineer passes over are part of the
+    /// path. Returns `None` if `to` is not a destination.
+    pub fn path_to(&self, to: SquareIndex) -> Option<Vec<SquareIndex>> {
+        if !self.contains(to) {
+            return None;
+        }
+        let mut path = vec![to];
+        let mut sq = to;
+        while sq != self.from {
+            sq = self.parent[sq as usize];
+            path.push(sq);
+        }
+        path.reverse();
+        Some(path)
+    }
+}
+
+impl GameState {
+    /// Finds every square the piece on `from` could reach if it moved like an
+    /// engineer: any distance along connected railroad, turning freely, as long
+    /// as no piece blocks the way. The first enemy piece met on each route can
+    /// be attacked. Returns an empty reach if `from` is empty or off the railroad.
+    pub fn rail_reach(&self, from: SquareIndex) -> RailReach {
+        let mut reach = RailReach {
+            from,
+            destinations: Bitboard::EMPTY,
+            parent: [NO_PARENT; NUM_SQUARES],
+        };
+        let piece = match self.board[from as usize] {
+            Some(p) if is_rail(from) => p,
+            _ => return reach,
+        };
+
+        // Breadth-first so that reconstructed paths are as short as possible
+        let mut queue = [0; NUM_SQUARES];
+        let (mut head, mut tail) = (0, 1);
+        queue[0] = from;
+        reach.parent[from as usize] = from;
+        while head < tail {
+            let sq = queue[head];
+            head += 1;
+            for to in RAIL_LINKS[sq as usize].iter() {
+                if reach.parent[to as usize] != NO_PARENT {
+                    continue;
+                }
+                reach.parent[to as usize] = sq;
+                if !self.occupancy.occupied().contains(to) {
+                    queue[tail] = to;
+                    tail += 1;
+                }
+                if self.can_land(piece, to) {
+                    reach.destinations.insert(to);
+                }
+            }
+        }
+        reach
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::{sq, Color, Piece, PieceType};
+
+    fn engineer_at(square: SquareIndex) -> GameState {
+        let mut state = GameState::new();
+        state.set_piece(
+            square,
+            Some(Piece {
+                ty: PieceType::Engineer,
+                color: Color::Red,
+            }),
+        );
+        state
+    }
+
+    #[test]
+    fn test_path_turns_corners() {
+        let state = engineer_at(sq::B3);
+        let reach = state.rail_reach(sq::B3);
+        assert!(!reach.contains(sq::B3));
+        assert!(!reach.contains(sq::G1));
+        assert_eq!(
+            reach.path_to(sq::D1),
+            Some(vec![sq::B3, sq::B2, sq::B1, sq::C1, sq::D1])
+        );
+        assert_eq!(reach.path_to(sq::C3), None);
+
+        // Crossing to the other half takes ten steps whichever way is used
+        let path = reach.path_to(sq::H3).unwrap();
+        assert_eq!(path.len(), 11);
+        assert_eq!((path[0], path[10]), (sq::B3, sq::H3));
+        for pair in path.windows(2) {
+            assert!(RAIL_LINKS[pair[0] as usize].contains(pair[1]));
+        }
+    }
+
+    #[test]
+    fn test_blocked_paths_detour() {
+        let mut state = engineer_at(sq::B3);
+        state.set_piece(
+            sq::E1,
+            Some(Piece {
+                ty: PieceType::Platoon,
+                color: Color::Red,
+            }),
+        );
+        state.set_piece(
+            sq::F4,
+            Some(Piece {
+                ty: PieceType::Platoon,
+                color: Color::Black,
+            }),
+        );
+        let reach = state.rail_reach(sq::B3);
+        assert!(!reach.contains(sq::E1));
+        assert!(reach.contains(sq::F4));
+        assert_eq!(
+            reach.path_to(sq::F3),
+            Some(vec![
+                sq::B3,
+                sq::B4,
+                sq::B5,
+                sq::C5,
+                sq::D5,
+                sq::E5,
+                sq::F5,
+                sq::G5,
+                sq::H5,
+                sq::H4,
+                sq::H3,
+                sq::G3,
+                sq::F3
+            ])
+        );
+    }
+
+    #[test]
+    fn test_reach_matches_move_generation() {
+        let state = GameState::random_start(99);
+        for from in 0..NUM_SQUARES as SquareIndex {
+            if state.board[from as usize].map(|p| p.ty) != Some(PieceType::Engineer) {
+                continue;
+            }
+            let reach = state.rail_reach(from);
+            let moves = state.moves_from(from);
+            for to in reach.destinations() {
+                assert!(moves.iter().any(|m| m.to == to));
+            }
+        }
+    }
+}
@@ -0,0 +1,311 @@
+//! Limits on repeating moves, set by `Rules::repetition`.
+//!
+//! Competitive rules stop a player from stalling by moving one piece back and
+//! forth between the same two squares, and from chasing the same enemy piece
+//! around the board forever. Both are counted over the side's own consecutive
+//! moves with one piece, using the game's `history`:
+//!
+//! - a *shuttle* is a move straight back to the square the piece just left;
+//! - a *chase* is a move after which the piece can attack an enemy piece that
+//!   has just fled from it.
+//!
+//! [`GameState::make_move`] rejects moves that go over the limits, and
+//! [`GameState::allowed_moves`] leaves them out. [`GameState::legal_moves`]
+//! only knows how pieces move and still includes them. A side whose every
+//! legal move goes over the limits loses, as if it had no legal moves.
+
+use crate::bitboard::Bitboard;
+use crate::board::{GameState, SquareIndex};
+use crate::combat::MoveOutcome;
+use crate::history::MoveRecord;
+use crate::movegen::Move;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// How many times in a row a side may repeat itself.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RepetitionLimits {
+    /// Moves in a row one piece may make back and forth between two squares.
+    pub max_shuttles: u32,
+    /// Moves in a row one piece may make chasing the same enemy piece.
+    pub max_chases: u32,
+}
+
+impl Default for RepetitionLimits {
+    fn default() -> Self {
+        Self {
+            max_shuttles: 5,
+            max_chases: 5,
+        }
+    }
+}
+
+/// A move that breaks one of the [`RepetitionLimits`].
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RepetitionViolation {
+    /// The piece would make its `count`th back-and-forth move in a row.
+    Shuttle { count: u32 },
+    /// The piece would chase the same enemy piece for the `count`th time.
+    Chase { count: u32 },
+}
+
+impl fmt::Display for RepetitionViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RepetitionViolation::Shuttle { count } => {
+                write!(f, "piece would move back and forth {count} times in a row")
+            }
+            RepetitionViolation::Chase { count } => {
+                write!(f, "piece would chase the same piece {count} times in a row")
+            }
+        }
+    }
+}
+
+impl GameState {
+    /// The moves of the side to move that are legal and within the
+    /// repetition limits. The game is left as it was.
+    pub fn allowed_moves(&mut self) -> Vec<Move> {
+        let mut moves = self.legal_moves();
+        moves.retain(|&mv| self.repetition_violation(mv).is_none());
+        moves
+    }
+
+    /// Whether any of `moves`, legal moves for the side to move, is within
+    /// the repetition limits.
+    pub(crate) fn any_allowed(&self, moves: &[Move]) -> bool {
+        if moves.iter().any(|&mv| !self.may_repeat(mv)) {
+            return true;
+        }
+        // Every move is by the piece that moved last, which is rare enough
+        // to check on a copy
+        let mut state = self.clone();
+        moves
+            .iter()
+            .any(|&mv| state.repetition_violation(mv).is_none())
+    }
+
+    /// Whether `mv` could break a limit at all. Only the piece that made the
+    /// side's previous move can, unless chasing is not allowed at all.
+    fn may_repeat(&self, mv: Move) -> bool {
+        self.rules.repetition.max_chases == 0
+            || self.own_moves().next().map(|r| r.mv.to) == Some(mv.from)
+    }
+
+    /// Checks `mv`, a legal move for the side to move, against the repetition
+    /// limits. Chases are found by taking moves back and replaying them, so
+    /// the game is left as it was.
+    pub fn repetition_violation(&mut self, mv: Move) -> Option<RepetitionViolation> {
+        if !self.may_repeat(mv) {
+            return None;
+        }
+        let limits = self.rules.repetition;
+        let shuttles = self.shuttle_count(mv);
+        if shuttles > limits.max_shuttles {
+            return Some(RepetitionViolation::Shuttle { count: shuttles });
+        }
+        let chases = self.chase_count(mv);
+        if chases > limits.max_chases {
+            return Some(RepetitionViolation::Chase { count: chases });
+        }
+        None
+    }
+
+    /// How many times the current position has occurred before, with the
+    /// same side to move.
+    pub fn repetition_count(&self) -> usize {
+        self.history
+            .iter()
+            .filter(|r| r.key == self.zobrist)
+            .count()
+    }
+
+    /// The side to move's earlier moves, most recent first.
+    fn own_moves(&self) -> impl Iterator<Item = &MoveRecord> {
+        self.history.iter().rev().skip(1).step_by(2)
+    }
+
+    /// The number of back-and-forth moves in a row `mv` would complete,
+    /// counting the first move away, or 0 if `mv` does not go straight back
+    /// to the square the piece just left.
+    fn shuttle_count(&self, mv: Move) -> u32 {
+        let mut count = 1;
+        let mut next = mv;
+        for record in self.own_moves() {
+            if record.outcome != MoveOutcome::Moved || record.mv != Move::new(next.to, next.from) {
+                break;
+            }
+            count += 1;
+            next = record.mv;
+        }
+        if count == 1 {
+            0
+        } else {
+            count
+        }
+    }
+
+    /// Enemy squares the piece on `sq` could attack.
+    fn threats(&self, sq: SquareIndex) -> Bitboard {
+        let targets: Bitboard = self.moves_from(sq).iter().map(|m| m.to).collect();
+        targets & self.occupancy.occupied()
+    }
+
+    /// The number of times in a row `mv` would chase the same enemy piece,
+    /// or 0 if it threatens nothing.
+    fn chase_count(&mut self, mv: Move) -> u32 {
+        self.make_move_unchecked(mv);
+        // Moves taken back to look at earlier positions, replayed at the end
+        let mut taken_back = Vec::new();
+        let mut chaser = mv.to;
+        let mut targets = self.threats(chaser);
+        let mut count = 0;
+        while !targets.is_empty() {
+            count += 1;
+            let chase = self.unmake_move().expect("chasing move is recorded");
+            taken_back.push(chase.mv);
+            chaser = chase.mv.from;
+
+            // The target must have just fled from the chaser...
+            let Some(reply) = self.unmake_move() else {
+                break;
+            };
+            taken_back.push(reply.mv);
+            if reply.outcome != MoveOutcome::Moved || !targets.contains(reply.mv.to) {
+                break;
+            }
+            // ...which must have threatened it with the side's previous move
+            match self.last_move() {
+                Some(r) if r.outcome == MoveOutcome::Moved && r.mv.to == chaser => {}
+                _ => break,
+            }
+            targets = self.threats(chaser) & Bitboard::square(reply.mv.from);
+        }
+        for &mv in taken_back.iter().rev() {
+            self.make_move_unchecked(mv);
+        }
+        self.unmake_move();
+        count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::{sq, Color, PieceType};
+    use crate::combat::MoveError;
+
+    fn play(state: &mut GameState, moves: &[(SquareIndex, SquareIndex)]) {
+        for &(from, to) in moves {
+            state.make_move(Move::new(from, to)).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_shuttle_limit() {
+        let mut state = GameState::from_pieces(&[
+            (sq::D1, PieceType::Army, Color::Red),
+            (sq::L5, PieceType::Army, Color::Black),
+        ]);
+        state.rules.repetition.max_shuttles = 3;
+
+        play(
+            &mut state,
+            &[
+                (sq::D1, sq::C1),
+                (sq::L5, sq::K5),
+                (sq::C1, sq::D1),
+                (sq::K5, sq::J5),
+                (sq::D1, sq::C1),
+                (sq::J5, sq::I5),
+            ],
+        );
+        let back = Move::new(sq::C1, sq::D1);
+        assert_eq!(
+            state.repetition_violation(back),
+            Some(RepetitionViolation::Shuttle { count: 4 })
+        );
+        assert_eq!(
+            state.make_move(back),
+            Err(MoveError::Repetition(RepetitionViolation::Shuttle {
+                count: 4
+            }))
+        );
+        assert!(!state.allowed_moves().contains(&back));
+        assert!(state.allowed_moves().contains(&Move::new(sq::C1, sq::B1)));
+    }
+
+    #[test]
+    fn test_no_shuttles_allowed() {
+        let mut state = GameState::from_pieces(&[
+            (sq::D1, PieceType::Army, Color::Red),
+            (sq::L5, PieceType::Army, Color::Black),
+        ]);
+        state.rules.repetition.max_shuttles = 0;
+        assert_eq!(state.allowed_moves(), state.legal_moves());
+
+        play(&mut state, &[(sq::D1, sq::C1), (sq::L5, sq::K5)]);
+        assert_eq!(
+            state.repetition_violation(Move::new(sq::C1, sq::D1)),
+            Some(RepetitionViolation::Shuttle { count: 2 })
+        );
+        assert_eq!(state.repetition_violation(Move::new(sq::C1, sq::B1)), None);
+        assert_eq!(state.allowed_moves().len(), state.legal_moves().len() - 1);
+        assert!(state.any_allowed(&state.legal_moves()));
+    }
+
+    #[test]
+    fn test_chase_limit() {
+        // A Red army follows a Black platoon down the fifth column
+        let mut state = GameState::from_pieces(&[
+            (sq::B3, PieceType::Army, Color::Red),
+            (sq::E5, PieceType::Platoon, Color::Black),
+            (sq::M1, PieceType::Landmine, Color::Black),
+        ]);
+        state.rules.repetition.max_chases = 2;
+
+        play(
+            &mut state,
+            &[(sq::B3, sq::B5), (sq::E5, sq::F5), (sq::B5, sq::C5)],
+        );
+        assert_eq!(state.allowed_moves(), state.legal_moves());
+        play(&mut state, &[(sq::F5, sq::H5)]);
+
+        let chase = Move::new(sq::C5, sq::D5);
+        let (board, key, plies) = (
+            state.board.clone(),
+            state.zobrist_key(),
+            state.history.len(),
+        );
+        assert_eq!(
+            state.repetition_violation(chase),
+            Some(RepetitionViolation::Chase { count: 3 })
+        );
+        // The moves taken back to count the chase are replayed
+        assert_eq!(state.board, board);
+        assert_eq!(state.zobrist_key(), key);
+        assert_eq!(state.history.len(), plies);
+        // Stepping into a camp threatens nothing
+        assert_eq!(state.repetition_violation(Move::new(sq::C5, sq::C4)), None);
+    }
+
+    #[test]
+    fn test_repetition_count() {
+        let mut state = GameState::from_pieces(&[
+            (sq::D1, PieceType::Army, Color::Red),
+            (sq::L5, PieceType::Army, Color::Black),
+        ]);
+        for _ in 0..2 {
+            play(
+                &mut state,
+                &[
+                    (sq::D1, sq::C1),
+                    (sq::L5, sq::K5),
+                    (sq::C1, sq::D1),
+                    (sq::K5, sq::L5),
+                ],
+            );
+        }
+        assert_eq!(state.repetition_count(), 2);
+    }
+}
@@ -0,0 +1,271 @@
+use crate::board::{Color, GameState, Piece, PieceType, LIST_OF_PIECETYPES};
+use serde::{Deserialize, Serialize};
+
+/// Why a side won the game.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WinReason {
+    /// The opponent's flag was captured.
+    FlagCaptured,
+    /// The opponent has nothing left but landmines and its flag.
+    NoMovablePieces,
+    /// The opponent has movable pieces, but none of them can move.
+    NoLegalMoves,
+    /// Every move the opponent's pieces can make breaks the repetition
+    /// limits.
+    NoAllowedMoves,
+}
+
+/// Why the game was drawn.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DrawReason {
+    /// Both sides agreed to a draw.
+    Agreement,
+    /// No attack happened within the no-capture move limit.
+    NoCaptureLimit,
+    /// Neither side has a movable piece left.
+    NoMovablePieces,
+    /// Every player of both teams is out of a four-player game.
+    BothTeamsOut,
+}
+
+/// The state of the game from the referee's point of view.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameResult {
+    Ongoing,
+    RedWins(WinReason),
+    BlackWins(WinReason),
+    Draw(DrawReason),
+}
+
+impl GameResult {
+    fn win(color: Color, reason: WinReason) -> Self {
+        match color {
+            Color::Red => GameResult::RedWins(reason),
+            Color::Black => GameResult::BlackWins(reason),
+        }
+    }
+
+    /// Returns the winning color, if any.
+    pub fn winner(&self) -> Option<Color> {
+        match self {
+            GameResult::RedWins(_) => Some(Color::Red),
+            GameResult::BlackWins(_) => Some(Color::Black),
+            _ => None,
+        }
+    }
+
+    pub fn is_over(&self) -> bool {
+        *self != GameResult::Ongoing
+    }
+}
+
+impl GameState {
+    /// Returns whether `color` still has its flag on the board.
+    pub fn has_flag(&self, color: Color) -> bool {
+        !self
+            .occupancy
+            .piece(Piece {
+                ty: PieceType::Flag,
+                color,
+            })
+            .is_empty()
+    }
+
+    /// Returns whether `color` has any piece other than its flag and, unless
+    /// the rules count them, landmines.
+    pub fn has_movable_pieces(&self, color: Color) -> bool {
+        LIST_OF_PIECETYPES
+            .iter()
+            .filter(|&&ty| self.rules.is_movable(ty))
+            .any(|&ty| !self.occupancy.piece(Piece { ty, color }).is_empty())
+    }
+
+    /// Offers a draw on behalf of `color`.
+    pub fn offer_draw(&mut self, color: Color) {
+        self.draw_offer = Some(color);
+    }
+
+    /// Accepts the opponent's pending draw offer on behalf of `color`.
+    /// Returns false if there was no such offer.
+    pub fn accept_draw(&mut self, color: Color) -> bool {
+        if self.draw_offer == Some(color.other()) {
+            self.draw_offer = None;
+            self.draw_agreed = true;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Determines whether the game is over, and how.
+    pub fn result(&self) -> GameResult {
+        for color in [Color::Red, Color::Black] {
+            if !self.has_flag(color) {
+                return GameResult::win(color.other(), WinReason::FlagCaptured);
+            }
+        }
+
+        if self.draw_agreed {
+            return GameResult::Draw(DrawReason::Agreement);
+        }
+
+        let red_movable = self.has_movable_pieces(Color::Red);
+        let black_movable = self.has_movable_pieces(Color::Black);
+        match (red_movable, black_movable) {
+            (false, false) => return GameResult::Draw(DrawReason::NoMovablePieces),
+            (false, true) => return GameResult::BlackWins(WinReason::NoMovablePieces),
+            (true, false) => return GameResult::RedWins(WinReason::NoMovablePieces),
+            (true, true) => {}
+        }
+
+        let moves = self.legal_moves();
+        if moves.is_empty() {
+            return GameResult::win(self.turn.other(), WinReason::NoLegalMoves);
+        }
+        if !self.any_allowed(&moves) {
+            return GameResult::win(self.turn.other(), WinReason::NoAllowedMoves);
+        }
+
+        if self.plies_since_capture >= self.rules.no_capture_limit {
+            return GameResult::Draw(DrawReason::NoCaptureLimit);
+        }
+
+        GameResult::Ongoing
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::{sq, Piece, SquareIndex};
+    use crate::movegen::Move;
+
+    const FLAGS: [(SquareIndex, PieceType, Color); 2] = [
+        (sq::A2, PieceType::Flag, Color::Red),
+        (sq::M2, PieceType::Flag, Color::Black),
+    ];
+
+    /// Both flags in place plus one movable piece each.
+    fn minimal() -> GameState {
+        GameState::from_pieces(&[
+            FLAGS[0],
+            FLAGS[1],
+            (sq::B5, PieceType::Engineer, Color::Red),
+            (sq::L5, PieceType::Platoon, Color::Black),
+        ])
+    }
+
+    #[test]
+    fn test_random_start_is_ongoing() {
+        assert_eq!(GameState::random_start(1).result(), GameResult::Ongoing);
+    }
+
+    #[test]
+    fn test_flag_capture_wins() {
+        let mut state = GameState::from_pieces(&[
+            FLAGS[0],
+            FLAGS[1],
+            (sq::B5, PieceType::Engineer, Color::Red),
+            (sq::B2, PieceType::Platoon, Color::Black),
+        ]);
+        state.set_turn(Color::Black);
+        state.make_move(Move::new(sq::B2, sq::A2)).unwrap();
+        assert_eq!(
+            state.result(),
+            GameResult::BlackWins(WinReason::FlagCaptured)
+        );
+    }
+
+    #[test]
+    fn test_no_movable_pieces() {
+        let mut state = minimal();
+        // The engineer loses to the platoon
+        state.make_move(Move::new(sq::B5, sq::L5)).unwrap();
+        assert_eq!(
+            state.result(),
+            GameResult::BlackWins(WinReason::NoMovablePieces)
+        );
+
+        let mut state = minimal();
+        state.set_piece(
+            sq::L5,
+            Some(Piece {
+                ty: PieceType::Engineer,
+                color: Color::Black,
+            }),
+        );
+        state.make_move(Move::new(sq::B5, sq::L5)).unwrap();
+        assert_eq!(
+            state.result(),
+            GameResult::Draw(DrawReason::NoMovablePieces)
+        );
+    }
+
+    #[test]
+    fn test_no_allowed_moves() {
+        // Red's army can only move between B1 and A1
+        let mut state = GameState::from_pieces(&[
+            FLAGS[0],
+            FLAGS[1],
+            (sq::B1, PieceType::Army, Color::Red),
+            (sq::C1, PieceType::Landmine, Color::Red),
+            (sq::B2, PieceType::Landmine, Color::Red),
+            (sq::L5, PieceType::Platoon, Color::Black),
+        ]);
+        state.rules.repetition.max_shuttles = 3;
+        for (from, to) in [
+            (sq::B1, sq::A1),
+            (sq::L5, sq::K5),
+            (sq::A1, sq::B1),
+            (sq::K5, sq::J5),
+            (sq::B1, sq::A1),
+        ] {
+            state.make_move(Move::new(from, to)).unwrap();
+        }
+        assert_eq!(state.result(), GameResult::Ongoing);
+        state.make_move(Move::new(sq::J5, sq::I5)).unwrap();
+        assert_eq!(state.legal_moves(), vec![Move::new(sq::A1, sq::B1)]);
+        assert_eq!(
+            state.result(),
+            GameResult::BlackWins(WinReason::NoAllowedMoves)
+        );
+    }
+
+    #[test]
+    fn test_no_legal_moves() {
+        // Red's only movable piece is stuck in its HQ
+        let state = GameState::from_pieces(&[
+            FLAGS[0],
+            FLAGS[1],
+            (sq::A4, PieceType::Army, Color::Red),
+            (sq::L5, PieceType::Platoon, Color::Black),
+        ]);
+        assert_eq!(
+            state.result(),
+            GameResult::BlackWins(WinReason::NoLegalMoves)
+        );
+    }
+
+    #[test]
+    fn test_draw_rules() {
+        let mut state = minimal();
+        state.rules.no_capture_limit = 2;
+        state.make_move(Move::new(sq::B5, sq::B4)).unwrap();
+        assert_eq!(state.result(), GameResult::Ongoing);
+        state.make_move(Move::new(sq::L5, sq::L4)).unwrap();
+        assert_eq!(state.result(), GameResult::Draw(DrawReason::NoCaptureLimit));
+
+        let mut state = minimal();
+        state.offer_draw(Color::Red);
+        assert!(!state.accept_draw(Color::Red));
+        assert!(state.accept_draw(Color::Black));
+        assert_eq!(state.result(), GameResult::Draw(DrawReason::Agreement));
+
+        // Moving instead of accepting declines the offer
+        let mut state = minimal();
+        state.offer_draw(Color::Red);
+        state.make_move(Move::new(sq::B5, sq::B4)).unwrap();
+        state.make_move(Move::new(sq::L5, sq::L4)).unwrap();
+        assert!(!state.accept_draw(Color::Black));
+    }
+}
@@ -0,0 +1,159 @@
+//! House rules.
+//!
+//! [`Rules::default`] is the standard game that the rest of the crate
+//! documents. Each field turns one common variant on or off; a game keeps its
+//! rules in `GameState::rules`, where setup validation, move generation,
+//! combat, the repetition limits and the game result read them.
+
+use crate::board::{PieceType, DEFAULT_NO_CAPTURE_LIMIT, LIST_OF_PIECETYPES, NUM_PIECETYPES};
+use crate::combat::CombatResult;
+use crate::repetition::RepetitionLimits;
+use serde::{Deserialize, Serialize};
+
+/// The rule variants a game is played with.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rules {
+    /// How many pieces of each type a side starts with, indexed by
+    /// `PieceType as usize`.
+    pub piece_counts: [usize; NUM_PIECETYPES],
+    /// Bombs may start on the first row.
+    pub bombs_on_first_row: bool,
+    /// Landmines must start on the back two rows.
+    pub landmines_on_back_rows: bool,
+    /// The flag must start in one of the two headquarters. Otherwise it may
+    /// start anywhere on its own side.
+    pub flag_in_hq: bool,
+    /// Engineers that attack a landmine remove it. Otherwise they die like
+    /// any other piece.
+    pub engineers_defuse_landmines: bool,
+    /// A bomb that attacks the flag destroys it. Otherwise bombs may not
+    /// move onto the enemy flag.
+    pub bomb_can_attack_flag: bool,
+    /// Landmines count as movable pieces, so a side left with only landmines
+    /// and its flag is not lost for having no movable pieces.
+    pub landmines_count_as_movable: bool,
+    /// The game is drawn once `plies_since_capture` reaches this limit.
+    pub no_capture_limit: u32,
+    /// How often a side may repeat the same moves.
+    pub repetition: RepetitionLimits,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Self {
+            piece_counts: LIST_OF_PIECETYPES.map(|ty| ty.num_per_player()),
+            bombs_on_first_row: false,
+            landmines_on_back_rows: true,
+            flag_in_hq: true,
+            engineers_defuse_landmines: true,
+            bomb_can_attack_flag: true,
+            landmines_count_as_movable: false,
+            no_capture_limit: DEFAULT_NO_CAPTURE_LIMIT,
+            repetition: RepetitionLimits::default(),
+        }
+    }
+}
+
+impl Rules {
+    /// How many pieces of type `ty` a side starts with.
+    pub fn count(&self, ty: PieceType) -> usize {
+        self.piece_counts[ty as usize]
+    }
+
+    /// Whether a piece of type `ty` counts as movable for the game result.
+    pub fn is_movable(&self, ty: PieceType) -> bool {
+        match ty {
+            PieceType::Flag => false,
+            PieceType::Landmine => self.landmines_count_as_movable,
+            _ => true,
+        }
+    }
+
+    /// Resolves an attack of `attacker` on `defender`.
+    /// Bomb: destroys both pieces, whichever side it is on. A bomb barred
+    /// from the flag never attacks it, but loses if made to.
+    /// Landmine: destroys any attacker except bombs and, unless disabled,
+    /// engineers (which defuse it).
+    /// Otherwise the higher rank wins and equal ranks trade.
+    pub fn resolve_combat(&self, attacker: PieceType, defender: PieceType) -> CombatResult {
+        if attacker == PieceType::Bomb && defender == PieceType::Flag && !self.bomb_can_attack_flag
+        {
+            return CombatResult::DefenderWins;
+        }
+        if attacker == PieceType::Bomb || defender == PieceType::Bomb {
+            return CombatResult::BothRemoved;
+        }
+        if defender == PieceType::Landmine {
+            return if attacker == PieceType::Engineer && self.engineers_defuse_landmines {
+                CombatResult::AttackerWins
+            } else {
+                CombatResult::DefenderWins
+            };
+        }
+        match attacker.rank().cmp(&defender.rank()) {
+            std::cmp::Ordering::Greater => CombatResult::AttackerWins,
+            std::cmp::Ordering::Less => CombatResult::DefenderWins,
+            std::cmp::Ordering::Equal => CombatResult::BothRemoved,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::{sq, Color, GameState, Piece};
+    use crate::combat::MoveError;
+    use crate::movegen::Move;
+    use crate::result::{GameResult, WinReason};
+    use PieceType::*;
+
+    #[test]
+    fn test_combat_variants() {
+        let standard = Rules::default();
+        assert_eq!(standard.count(Company), 3);
+        assert_eq!(
+            standard.resolve_combat(Engineer, Landmine),
+            CombatResult::AttackerWins
+        );
+        assert_eq!(
+            standard.resolve_combat(Bomb, Flag),
+            CombatResult::BothRemoved
+        );
+
+        let house = Rules {
+            engineers_defuse_landmines: false,
+            bomb_can_attack_flag: false,
+            ..Rules::default()
+        };
+        assert_eq!(
+            house.resolve_combat(Engineer, Landmine),
+            CombatResult::DefenderWins
+        );
+        assert_eq!(house.resolve_combat(Bomb, Flag), CombatResult::DefenderWins);
+        assert_eq!(house.resolve_combat(Bomb, Army), CombatResult::BothRemoved);
+    }
+
+    #[test]
+    fn test_bomb_barred_from_flag() {
+        let mut state = GameState::new();
+        state.rules.bomb_can_attack_flag = false;
+        for (square, ty, color) in [
+            (sq::L2, Bomb, Color::Red),
+            (sq::M2, Flag, Color::Black),
+            (sq::A2, Flag, Color::Red),
+            (sq::B1, Army, Color::Red),
+            (sq::L5, Army, Color::Black),
+        ] {
+            state.set_piece(square, Some(Piece { ty, color }));
+        }
+        let attack = Move::new(sq::L2, sq::M2);
+        assert!(!state.legal_moves().contains(&attack));
+        assert!(state.legal_moves().contains(&Move::new(sq::L2, sq::L1)));
+        assert_eq!(state.make_move(attack), Err(MoveError::Illegal));
+
+        state.rules.bomb_can_attack_flag = true;
+        assert!(state.legal_moves().contains(&attack));
+        state.make_move(Move::new(sq::L2, sq::M2)).unwrap();
+        assert_eq!(state.result(), GameResult::RedWins(WinReason::FlagCaptured));
+    }
+}
@@ -0,0 +1,675 @@
+//! Starting positions: each side's [`Deployment`] and the rules it must follow.
+
+use crate::board::{
+    sq, Color, GameState, Piece, PieceType, SquareIndex, SquareType, LIST_OF_PIECETYPES,
+    NUM_PIECETYPES, NUM_SQUARES, SQUARE_TO_SQUARETYPE,
+};
+use crate::prng::PseudoRng;
+use crate::rules::Rules;
+use crate::square::Square;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// A single reason a starting position is not legal.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum SetupViolation {
+    /// The board does not have 65 squares.
+    BoardSize(usize),
+    /// A side has the wrong number of pieces of one type.
+    WrongCount {
+        color: Color,
+        ty: PieceType,
+        expected: usize,
+        found: usize,
+    },
+    /// Pieces cannot start on camps, frontline squares or mountains.
+    ForbiddenSquare { square: Square, piece: Piece },
+    /// A piece is placed in the opponent's half.
+    OutsideOwnSide { square: Square, piece: Piece },
+    /// Bombs cannot start on the first row.
+    BombOnFirstRow { square: Square, color: Color },
+    /// Landmines must start on the back two rows.
+    LandmineOutsideBackRows { square: Square, color: Color },
+    /// The flag must start in a headquarters.
+    FlagOutsideHq { square: Square, color: Color },
+    /// The piece counts leave no room for a legal deployment: `needed`
+    /// pieces, including every `ty`, can only start on the `squares` squares
+    /// that `ty` may start on.
+    NoRoom {
+        color: Color,
+        ty: PieceType,
+        needed: usize,
+        squares: usize,
+    },
+}
+
+impl fmt::Display for SetupViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SetupViolation::BoardSize(n) => write!(f, "board has {n} squares instead of 65"),
+            SetupViolation::WrongCount {
+                color,
+                ty,
+                expected,
+                found,
+            } => write!(f, "{color:?} has {found} {ty:?} instead of {expected}"),
+            SetupViolation::ForbiddenSquare { square, piece } => write!(
+                f,
+                "{:?} {:?} on {square}: pieces cannot start on a {:?} square",
+                piece.color,
+                piece.ty,
+                square.square_type()
+            ),
+            SetupViolation::OutsideOwnSide { square, piece } => write!(
+                f,
+                "{:?} {:?} on {square} is outside its own half",
+                piece.color, piece.ty
+            ),
+            SetupViolation::BombOnFirstRow { square, color } => {
+                write!(
+                    f,
+                    "{color:?} bomb on {square}: bombs cannot start on the first row"
+                )
+            }
+            SetupViolation::LandmineOutsideBackRows { square, color } => write!(
+                f,
+                "{color:?} landmine on {square}: landmines must start on the back two rows"
+            ),
+            SetupViolation::FlagOutsideHq { square, color } => {
+                write!(
+                    f,
+                    "{color:?} flag on {square}: the flag must start in a headquarters"
+                )
+            }
+            SetupViolation::NoRoom {
+                color,
+                ty,
+                needed,
+                squares,
+            } => write!(
+                f,
+                "{color:?} has {needed} pieces for the {squares} squares its {ty:?} may start on"
+            ),
+        }
+    }
+}
+
+/// One side's pieces before the game starts, covering the 25 squares of
+/// [`sq::RED_SIDE`] or [`sq::BLACK_SIDE`]. Each player builds a deployment in
+/// secret and [`GameState::from_deployments`] merges the two.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Deployment {
+    color: Color,
+    board: Vec<Option<PieceType>>,
+}
+
+impl Deployment {
+    /// An empty deployment for `color`.
+    pub fn new(color: Color) -> Self {
+        Self {
+            color,
+            board: vec![None; NUM_SQUARES],
+        }
+    }
+
+    /// A random deployment for `color` that is legal under `rules`. Fails if
+    /// the piece counts in `rules` leave no room for a legal deployment.
+    pub fn random(
+        color: Color,
+        rules: &Rules,
+        rng: &mut PseudoRng,
+    ) -> Result<Self, SetupViolation> {
+        check_room(rules, color)?;
+        let mut deployment = Deployment::new(color);
+        let mut available = side_squares(color).to_vec();
+
+        // The flag, landmines and bombs may be kept off some squares. Each
+        // type is placed in turn, the one with the fewest allowed squares
+        // first, so that none takes a square a more restricted type needs
+        let mut restricted = [PieceType::Flag, PieceType::Landmine, PieceType::Bomb];
+        restricted.sort_by_key(|&ty| allowed_squares(rules, color, ty).len());
+        for ty in restricted {
+            for _ in 0..rules.count(ty) {
+                let choices: Vec<usize> = available
+                    .iter()
+                    .enumerate()
+                    .filter(|&(_, &s)| can_start_on(rules, Piece { ty, color }, s))
+                    .map(|(i, _)| i)
+                    .collect();
+                let idx = choices[rng.gen_range(0..choices.len())];
+                let sq = available.remove(idx);
+                deployment.place(sq, ty);
+            }
+        }
+
+        // Remaining piece types
+        let mut remaining: Vec<PieceType> = Vec::new();
+        for ty in LIST_OF_PIECETYPES.iter() {
+            if *ty == PieceType::Flag || *ty == PieceType::Bomb || *ty == PieceType::Landmine {
+                continue;
+            }
+            for _ in 0..rules.count(*ty) {
+                remaining.push(*ty);
+            }
+        }
+
+        while !remaining.is_empty() {
+            let sq_idx = rng.gen_range(0..available.len());
+            let sq = available.remove(sq_idx);
+            let ty_idx = rng.gen_range(0..remaining.len());
+            let ty = remaining.remove(ty_idx);
+            deployment.place(sq, ty);
+        }
+
+        Ok(deployment)
+    }
+
+    pub fn color(&self) -> Color {
+        self.color
+    }
+
+    /// The piece type on `sq`, if any.
+    pub fn get(&self, sq: SquareIndex) -> Option<PieceType> {
+        self.board[sq as usize]
+    }
+
+    /// Puts a piece on `sq` and returns the piece it replaced. Squares outside
+    /// the side are accepted here and reported by [`Deployment::validate`].
+    pub fn place(&mut self, sq: SquareIndex, ty: PieceType) -> Option<PieceType> {
+        self.board[sq as usize].replace(ty)
+    }
+
+    /// Clears `sq` and returns the piece that was there.
+    pub fn remove(&mut self, sq: SquareIndex) -> Option<PieceType> {
+        self.board[sq as usize].take()
+    }
+
+    /// The occupied squares and their pieces, in index order.
+    pub fn pieces(&self) -> impl Iterator<Item = (SquareIndex, Piece)> + '_ {
+        let color = self.color;
+        self.board
+            .iter()
+            .enumerate()
+            .filter_map(move |(i, ty)| ty.map(|ty| (i as SquareIndex, Piece { ty, color })))
+    }
+
+    /// Checks the deployment against the setup `rules` for its own side. An
+    /// empty list means it can be used to start a game.
+    pub fn validate(&self, rules: &Rules) -> Vec<SetupViolation> {
+        let mut violations = Vec::new();
+        if self.board.len() != NUM_SQUARES {
+            violations.push(SetupViolation::BoardSize(self.board.len()));
+            return violations;
+        }
+        check_counts(
+            rules,
+            self.color,
+            self.board.iter().flatten().copied(),
+            &mut violations,
+        );
+        for (sq, piece) in self.pieces() {
+            check_piece(rules, sq, piece, &mut violations);
+        }
+        violations
+    }
+}
+
+impl GameState {
+    /// Starts a game played with `rules` from each player's deployment. Each
+    /// deployment and then the merged position are validated, and every
+    /// violation found is returned.
+    pub fn from_deployments(
+        red: &Deployment,
+        black: &Deployment,
+        rules: Rules,
+    ) -> Result<Self, Vec<SetupViolation>> {
+        let mut violations = red.validate(&rules);
+        violations.extend(black.validate(&rules));
+        if !violations.is_empty() {
+            return Err(violations);
+        }
+        let mut state = GameState::with_rules(rules);
+        state.place_deployment(red);
+        state.place_deployment(black);
+        let violations = state.validate_startpos();
+        if violations.is_empty() {
+            Ok(state)
+        } else {
+            Err(violations)
+        }
+    }
+
+    /// Extracts `color`'s pieces on its own side of the board.
+    pub fn deployment(&self, color: Color) -> Deployment {
+        let mut deployment = Deployment::new(color);
+        for &sq in side_squares(color) {
+            if let Some(p) = self.board[sq as usize].filter(|p| p.color == color) {
+                deployment.place(sq, p.ty);
+            }
+        }
+        deployment
+    }
+
+    pub(crate) fn place_deployment(&mut self, deployment: &Deployment) {
+        for (sq, piece) in deployment.pieces() {
+            self.board[sq as usize] = Some(piece);
+        }
+        self.refresh();
+    }
+
+    /// Checks the position against the setup rules in `self.rules` and
+    /// returns every violation
+    /// found. An empty list means the position is a legal start.
+    pub fn validate_startpos(&self) -> Vec<SetupViolation> {
+        let mut violations = Vec::new();
+        if self.board.len() != NUM_SQUARES {
+            violations.push(SetupViolation::BoardSize(self.board.len()));
+            return violations;
+        }
+
+        for color in [Color::Red, Color::Black] {
+            let pieces = self.board.iter().flatten().filter(|p| p.color == color);
+            check_counts(&self.rules, color, pieces.map(|p| p.ty), &mut violations);
+        }
+
+        for (idx, opt) in self.board.iter().enumerate() {
+            if let Some(piece) = *opt {
+                check_piece(&self.rules, idx as SquareIndex, piece, &mut violations);
+            }
+        }
+
+        violations
+    }
+}
+
+fn side_squares(color: Color) -> &'static [SquareIndex; 25] {
+    match color {
+        Color::Red => &sq::RED_SIDE,
+        Color::Black => &sq::BLACK_SIDE,
+    }
+}
+
+/// The squares on `color`'s side where a piece of type `ty` may start.
+fn allowed_squares(rules: &Rules, color: Color, ty: PieceType) -> Vec<SquareIndex> {
+    side_squares(color)
+        .iter()
+        .copied()
+        .filter(|&s| can_start_on(rules, Piece { ty, color }, s))
+        .collect()
+}
+
+/// Checks that `rules` leaves room for all of `color`'s pieces. The squares
+/// each type may start on are nested (the headquarters, the back rows, all
+/// but the first row, the whole side), so there is room as long as each of
+/// these holds every piece that is confined to it.
+fn check_room(rules: &Rules, color: Color) -> Result<(), SetupViolation> {
+    let mut types = LIST_OF_PIECETYPES.map(|ty| (allowed_squares(rules, color, ty).len(), ty));
+    types.sort_by_key(|&(squares, _)| squares);
+    let mut needed = 0;
+    for (squares, ty) in types {
+        needed += rules.count(ty);
+        if needed > squares {
+            return Err(SetupViolation::NoRoom {
+                color,
+                ty,
+                needed,
+                squares,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Checks that `color` has the right number of each piece type.
+fn check_counts(
+    rules: &Rules,
+    color: Color,
+    pieces: impl Iterator<Item = PieceType>,
+    violations: &mut Vec<SetupViolation>,
+) {
+    let mut counts = [0; NUM_PIECETYPES];
+    for ty in pieces {
+        counts[ty as usize] += 1;
+    }
+    for ty in LIST_OF_PIECETYPES {
+        let found = counts[ty as usize];
+        if found != rules.count(ty) {
+            violations.push(SetupViolation::WrongCount {
+                color,
+                ty,
+                expected: rules.count(ty),
+                found,
+            });
+        }
+    }
+}
+
+/// Whether `piece` may start the game on `sq` under `rules`, leaving aside
+/// how many pieces of each type there are.
+pub fn can_start_on(rules: &Rules, piece: Piece, sq: SquareIndex) -> bool {
+    let mut violations = Vec::new();
+    check_piece(rules, sq, piece, &mut violations);
+    violations.is_empty()
+}
+
+/// Checks where a single piece starts.
+fn check_piece(rules: &Rules, sq: SquareIndex, piece: Piece, violations: &mut Vec<SetupViolation>) {
+    let square = Square::new(sq).unwrap();
+
+    // pieces only on Empty/Railroad/HQ
+    match SQUARE_TO_SQUARETYPE[sq as usize] {
+        SquareType::Empty | SquareType::Railroad | SquareType::HQ => {}
+        _ => {
+            violations.push(SetupViolation::ForbiddenSquare { square, piece });
+            return;
+        }
+    }
+
+    let (hq, first_row, back_rows) = if piece.color == Color::Red {
+        (
+            &sq::RED_HQ[..],
+            &sq::RED_FIRST_ROW[..],
+            &sq::RED_BACK_ROWS[..],
+        )
+    } else {
+        (
+            &sq::BLACK_HQ[..],
+            &sq::BLACK_FIRST_ROW[..],
+            &sq::BLACK_BACK_ROWS[..],
+        )
+    };
+
+    if !side_squares(piece.color).contains(&sq) {
+        violations.push(SetupViolation::OutsideOwnSide { square, piece });
+        return;
+    }
+
+    let color = piece.color;
+    if piece.ty == PieceType::Bomb && !rules.bombs_on_first_row && first_row.contains(&sq) {
+        violations.push(SetupViolation::BombOnFirstRow { square, color });
+    }
+
+    if piece.ty == PieceType::Landmine && rules.landmines_on_back_rows && !back_rows.contains(&sq) {
+        violations.push(SetupViolation::LandmineOutsideBackRows { square, color });
+    }
+
+    if piece.ty == PieceType::Flag && rules.flag_in_hq && !hq.contains(&sq) {
+        violations.push(SetupViolation::FlagOutsideHq { square, color });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square(name: &str) -> Square {
+        name.parse().unwrap()
+    }
+
+    fn swap(state: &mut GameState, a: SquareIndex, b: SquareIndex) {
+        let (pa, pb) = (state.board[a as usize], state.board[b as usize]);
+        state.set_piece(a, pb);
+        state.set_piece(b, pa);
+    }
+
+    fn find(state: &GameState, piece: Piece) -> SquareIndex {
+        state.board.iter().position(|&p| p == Some(piece)).unwrap() as SquareIndex
+    }
+
+    #[test]
+    fn test_random_start_has_no_violations() {
+        for seed in 0..50 {
+            assert_eq!(GameState::random_start(seed).validate_startpos(), vec![]);
+        }
+    }
+
+    #[test]
+    fn test_bomb_on_first_row() {
+        let mut state = GameState::random_start(3);
+        let bomb = find(
+            &state,
+            Piece {
+                ty: PieceType::Bomb,
+                color: Color::Black,
+            },
+        );
+        swap(&mut state, bomb, sq::H3);
+        assert_eq!(
+            state.validate_startpos(),
+            vec![SetupViolation::BombOnFirstRow {
+                square: square("h3"),
+                color: Color::Black
+            }]
+        );
+        assert!(!state.is_startpos_legal());
+        assert_eq!(
+            state.validate_startpos()[0].to_string(),
+            "Black bomb on h3: bombs cannot start on the first row"
+        );
+    }
+
+    #[test]
+    fn test_wrong_counts() {
+        let mut state = GameState::random_start(4);
+        // Turn a Red officer in the back rows into a fourth landmine
+        let officer = sq::RED_BACK_ROWS
+            .iter()
+            .copied()
+            .find(|&s| state.board[s as usize].unwrap().ty.rank() > 0)
+            .unwrap();
+        let ty = state.board[officer as usize].unwrap().ty;
+        state.set_piece(
+            officer,
+            Some(Piece {
+                ty: PieceType::Landmine,
+                color: Color::Red,
+            }),
+        );
+        let violations = state.validate_startpos();
+        assert!(violations.contains(&SetupViolation::WrongCount {
+            color: Color::Red,
+            ty: PieceType::Landmine,
+            expected: 3,
+            found: 4
+        }));
+        assert!(violations.contains(&SetupViolation::WrongCount {
+            color: Color::Red,
+            ty,
+            expected: ty.num_per_player(),
+            found: ty.num_per_player() - 1
+        }));
+        assert_eq!(violations.len(), 2);
+    }
+
+    #[test]
+    fn test_misplaced_pieces() {
+        let mut state = GameState::new();
+        state.set_piece(
+            sq::A3,
+            Some(Piece {
+                ty: PieceType::Flag,
+                color: Color::Red,
+            }),
+        );
+        state.set_piece(
+            sq::E2,
+            Some(Piece {
+                ty: PieceType::Army,
+                color: Color::Red,
+            }),
+        );
+        state.set_piece(
+            sq::M1,
+            Some(Piece {
+                ty: PieceType::Army,
+                color: Color::Red,
+            }),
+        );
+        let violations = state.validate_startpos();
+        assert!(violations.contains(&SetupViolation::FlagOutsideHq {
+            square: square("a3"),
+            color: Color::Red
+        }));
+        assert!(violations.contains(&SetupViolation::ForbiddenSquare {
+            square: square("e2"),
+            piece: Piece {
+                ty: PieceType::Army,
+                color: Color::Red
+            }
+        }));
+        assert!(violations.contains(&SetupViolation::OutsideOwnSide {
+            square: square("m1"),
+            piece: Piece {
+                ty: PieceType::Army,
+                color: Color::Red
+            }
+        }));
+    }
+
+    #[test]
+    fn test_deployments_combine() {
+        let state = GameState::random_start(21);
+        let red = state.deployment(Color::Red);
+        let black = state.deployment(Color::Black);
+        assert_eq!(red.validate(&Rules::default()), vec![]);
+        assert_eq!(red.pieces().count(), 25);
+        assert!(black.pieces().all(|(_, p)| p.color == Color::Black));
+
+        let merged = GameState::from_deployments(&red, &black, Rules::default()).unwrap();
+        assert_eq!(merged.board, state.board);
+        assert_eq!(merged.turn, Color::Red);
+
+        // Two Black deployments leave Red without pieces
+        let err = GameState::from_deployments(&black, &black, Rules::default()).unwrap_err();
+        assert!(err.contains(&SetupViolation::WrongCount {
+            color: Color::Red,
+            ty: PieceType::Flag,
+            expected: 1,
+            found: 0
+        }));
+    }
+
+    #[test]
+    fn test_invalid_deployment_rejected() {
+        let mut rng = PseudoRng::new(8);
+        let rules = Rules::default();
+        let mut red = Deployment::random(Color::Red, &rules, &mut rng).unwrap();
+        let black = Deployment::random(Color::Black, &rules, &mut rng).unwrap();
+        assert_eq!(black.validate(&rules), vec![]);
+
+        let bomb = red
+            .pieces()
+            .find(|&(_, p)| p.ty == PieceType::Bomb)
+            .unwrap()
+            .0;
+        let front = red.remove(sq::F2).unwrap();
+        red.place(bomb, front);
+        red.place(sq::F2, PieceType::Bomb);
+        let expected = vec![SetupViolation::BombOnFirstRow {
+            square: square("f2"),
+            color: Color::Red,
+        }];
+        assert_eq!(red.validate(&rules), expected);
+        assert_eq!(
+            GameState::from_deployments(&red, &black, rules).unwrap_err(),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_wrong_size_deployment() {
+        let state = GameState::random_start(2);
+        let red = state.deployment(Color::Red);
+        let mut black = state.deployment(Color::Black);
+        black.board.resize(70, Some(PieceType::Army));
+        assert_eq!(
+            black.validate(&Rules::default()),
+            vec![SetupViolation::BoardSize(70)]
+        );
+        assert_eq!(
+            GameState::from_deployments(&red, &black, Rules::default()).unwrap_err(),
+            vec![SetupViolation::BoardSize(70)]
+        );
+    }
+
+    #[test]
+    fn test_house_rules() {
+        let rules = Rules {
+            bombs_on_first_row: true,
+            landmines_on_back_rows: false,
+            flag_in_hq: false,
+            ..Rules::default()
+        };
+        for seed in 0..20 {
+            let state = GameState::random_start_with_rules(seed, rules).unwrap();
+            assert_eq!(state.validate_startpos(), vec![]);
+        }
+
+        // The bomb on the first row is only a violation under standard rules
+        let mut state = GameState::random_start(3);
+        let bomb = find(
+            &state,
+            Piece {
+                ty: PieceType::Bomb,
+                color: Color::Black,
+            },
+        );
+        swap(&mut state, bomb, sq::H3);
+        state.rules = rules;
+        assert_eq!(state.validate_startpos(), vec![]);
+
+        // Clubs that play without field marshals
+        let mut counts = Rules::default().piece_counts;
+        counts[PieceType::Overall as usize] = 0;
+        counts[PieceType::Platoon as usize] += 1;
+        let rules = Rules {
+            piece_counts: counts,
+            ..Rules::default()
+        };
+        let red = Deployment::random(Color::Red, &rules, &mut PseudoRng::new(5)).unwrap();
+        assert_eq!(red.validate(&rules), vec![]);
+        assert_eq!(red.validate(&Rules::default()).len(), 2);
+    }
+
+    #[test]
+    fn test_counts_that_do_not_fit() {
+        // Eleven landmines for the ten squares of the back two rows
+        let mut piece_counts = Rules::default().piece_counts;
+        piece_counts[PieceType::Landmine as usize] = 11;
+        piece_counts[PieceType::Platoon as usize] = 0;
+        piece_counts[PieceType::Company as usize] = 0;
+        piece_counts[PieceType::Engineer as usize] = 1;
+        let rules = Rules {
+            piece_counts,
+            ..Rules::default()
+        };
+        let expected = SetupViolation::NoRoom {
+            color: Color::Black,
+            ty: PieceType::Landmine,
+            needed: 12,
+            squares: 10,
+        };
+        let mut rng = PseudoRng::new(1);
+        assert_eq!(
+            Deployment::random(Color::Black, &rules, &mut rng),
+            Err(expected)
+        );
+        let rules = Rules {
+            landmines_on_back_rows: false,
+            ..rules
+        };
+        assert!(Deployment::random(Color::Black, &rules, &mut rng).is_ok());
+
+        // More pieces than squares
+        let mut piece_counts = Rules::default().piece_counts;
+        piece_counts[PieceType::Engineer as usize] += 1;
+        let rules = Rules {
+            piece_counts,
+            ..Rules::default()
+        };
+        assert!(matches!(
+            GameState::random_start_with_rules(1, rules),
+            Err(SetupViolation::NoRoom { needed: 26, .. })
+        ));
+    }
+}
@@ -0,0 +1,182 @@
+//! Square names in the `a1`–`m5` scheme used by [`crate::board::sq`]: a row
+//! letter from `a` (Red's back row) to `m` (Black's back row) followed by a
+//! column number from 1 to 5.
+
+use crate::board::{SquareIndex, SquareType, NUM_SQUARES, SQUARE_TO_SQUARETYPE};
+use crate::movegen::Move;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+pub const NUM_ROWS: u8 = 13;
+pub const NUM_COLS: u8 = 5;
+
+/// A square on the board, named like `e3`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(transparent)]
+pub struct Square(SquareIndex);
+
+impl Square {
+    /// Returns the square with the given index, if it is on the board.
+    pub const fn new(index: SquareIndex) -> Option<Self> {
+        if (index as usize) < NUM_SQUARES {
+            Some(Square(index))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the square on `row` (0 for `a` up to 12 for `m`) and `col`
+    /// (0 for column 1 up to 4 for column 5).
+    pub const fn from_row_col(row: u8, col: u8) -> Option<Self> {
+        if row < NUM_ROWS && col < NUM_COLS {
+            Some(Square((NUM_ROWS - 1 - row) * NUM_COLS + col))
+        } else {
+            None
+        }
+    }
+
+    pub const fn index(self) -> SquareIndex {
+        self.0
+    }
+
+    /// The row, counted from 0 for `a` up to 12 for `m`.
+    pub const fn row(self) -> u8 {
+        NUM_ROWS - 1 - self.0 / NUM_COLS
+    }
+
+    /// The column, counted from 0 for column 1 up to 4 for column 5.
+    pub const fn col(self) -> u8 {
+        self.0 % NUM_COLS
+    }
+
+    /// The row letter, `a` to `m`.
+    pub const fn row_char(self) -> char {
+        (b'a' + self.row()) as char
+    }
+
+    pub fn square_type(self) -> &'static SquareType {
+        &SQUARE_TO_SQUARETYPE[self.0 as usize]
+    }
+}
+
+impl From<Square> for SquareIndex {
+    fn from(sq: Square) -> Self {
+        sq.0
+    }
+}
+
+impl TryFrom<SquareIndex> for Square {
+    type Error = SquareIndex;
+
+    fn try_from(index: SquareIndex) -> Result<Self, Self::Error> {
+        Square::new(index).ok_or(index)
+    }
+}
+
+impl fmt::Display for Square {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.row_char(), self.col() + 1)
+    }
+}
+
+/// Error returned when parsing a square or move name fails.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseSquareError(pub String);
+
+impl fmt::Display for ParseSquareError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid square name '{}'", self.0)
+    }
+}
+
+impl std::error::Error for ParseSquareError {}
+
+impl FromStr for Square {
+    type Err = ParseSquareError;
+
+    /// Parses names like `e3`. The row letter may be upper or lower case.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || ParseSquareError(s.to_string());
+        let bytes = s.as_bytes();
+        if bytes.len() != 2 {
+            return Err(err());
+        }
+        let row = bytes[0].to_ascii_lowercase().wrapping_sub(b'a');
+        let col = bytes[1].wrapping_sub(b'1');
+        Square::from_row_col(row, col).ok_or_else(err)
+    }
+}
+
+impl fmt::Display for Move {
+    /// Writes the move as `from-to`, e.g. `b3-h3`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (Square::new(self.from), Square::new(self.to)) {
+            (Some(from), Some(to)) => write!(f, "{from}-{to}"),
+            _ => write!(f, "{}-{}", self.from, self.to),
+        }
+    }
+}
+
+impl FromStr for Move {
+    type Err = ParseSquareError;
+
+    /// Parses moves written as `from-to`, e.g. `b3-h3`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (from, to) = s
+            .split_once('-')
+            .ok_or_else(|| ParseSquareError(s.to_string()))?;
+        Ok(Move::new(
+            from.parse::<Square>()?.index(),
+            to.parse::<Square>()?.index(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::sq;
+
+    #[test]
+    fn test_names_match_constants() {
+        assert_eq!("m1".parse(), Ok(Square(sq::M1)));
+        assert_eq!("A5".parse(), Ok(Square(sq::A5)));
+        assert_eq!("e3".parse::<Square>().unwrap().index(), sq::E3);
+        assert_eq!(Square(sq::G2).to_string(), "g2");
+        for i in 0..NUM_SQUARES as SquareIndex {
+            let square = Square::new(i).unwrap();
+            assert_eq!(square.to_string().parse(), Ok(square));
+            assert_eq!(
+                Square::from_row_col(square.row(), square.col()),
+                Some(square)
+            );
+        }
+    }
+
+    #[test]
+    fn test_accessors() {
+        let square: Square = "j2".parse().unwrap();
+        assert_eq!(square.row(), 9);
+        assert_eq!(square.col(), 1);
+        assert_eq!(square.row_char(), 'j');
+        assert_eq!(*square.square_type(), SquareType::Empty);
+        assert_eq!(*Square(sq::A2).square_type(), SquareType::HQ);
+    }
+
+    #[test]
+    fn test_invalid_names() {
+        for name in ["", "e", "e0", "e6", "n1", "e31", "3e", "é3"] {
+            assert!(name.parse::<Square>().is_err(), "{name}");
+        }
+        assert_eq!(Square::new(65), None);
+    }
+
+    #[test]
+    fn test_move_names() {
+        let mv: Move = "b3-h3".parse().unwrap();
+        assert_eq!(mv, Move::new(sq::B3, sq::H3));
+        assert_eq!(mv.to_string(), "b3-h3");
+        assert!("b3h3".parse::<Move>().is_err());
+    }
+}
@@ -0,0 +1,314 @@
+//! Symmetries of the board.
+//!
+//! The board looks the same after flipping it top to bottom, which swaps
+//! Red's and Black's halves, and after mirroring it left to right, which keeps
+//! each half in place. Flipping a position and swapping the color of every
+//! piece gives the same game with the players' roles exchanged.
+//!
+//! The transforms apply to the whole game, history included, so that moves
+//! can still be taken back and repetitions are still counted afterwards.
+
+use crate::board::{Color, GameState, Piece, SquareIndex, NUM_SQUARES};
+use crate::combat::MoveOutcome;
+use crate::history::MoveRecord;
+use crate::movegen::Move;
+use crate::setup::Deployment;
+use crate::square::{NUM_COLS, NUM_ROWS};
+
+/// The square at the same column on the opposite row, e.g. `b1` to `l1`.
+pub const fn flip_square(sq: SquareIndex) -> SquareIndex {
+    (NUM_ROWS - 1 - sq / NUM_COLS) * NUM_COLS + sq % NUM_COLS
+}
+
+/// The square on the same row in the opposite column, e.g. `b1` to `b5`.
+pub const fn mirror_square(sq: SquareIndex) -> SquareIndex {
+    sq / NUM_COLS * NUM_COLS + (NUM_COLS - 1 - sq % NUM_COLS)
+}
+
+impl Move {
+    /// The move with both squares flipped top to bottom.
+    pub fn flipped(self) -> Self {
+        Move::new(flip_square(self.from), flip_square(self.to))
+    }
+
+    /// The move with both squares mirrored left to right.
+    pub fn mirrored(self) -> Self {
+        Move::new(mirror_square(self.from), mirror_square(self.to))
+    }
+}
+
+impl MoveRecord {
+    fn map_squares(self, f: fn(SquareIndex) -> SquareIndex) -> Self {
+        Self {
+            mv: Move::new(f(self.mv.from), f(self.mv.to)),
+            ..self
+        }
+    }
+
+    fn color_swapped(self) -> Self {
+        let outcome = match self.outcome {
+            MoveOutcome::Moved => MoveOutcome::Moved,
+            MoveOutcome::Attack {
+                attacker,
+                defender,
+                result,
+            } => MoveOutcome::Attack {
+                attacker: swap_color(attacker),
+                defender: swap_color(defender),
+                result,
+            },
+            MoveOutcome::FlagCaptured { attacker, flag } => MoveOutcome::FlagCaptured {
+                attacker: swap_color(attacker),
+                flag: swap_color(flag),
+            },
+        };
+        let [red, black] = self.flags_revealed;
+        Self {
+            outcome,
+            to_piece: self.to_piece.map(swap_color),
+            draw_offer: self.draw_offer.map(|c| c.other()),
+            flags_revealed: [black, red],
+            ..self
+        }
+    }
+}
+
+fn swap_color(p: Piece) -> Piece {
+    Piece {
+        ty: p.ty,
+        color: p.color.other(),
+    }
+}
+
+impl GameState {
+    fn map_squares(&self, f: fn(SquareIndex) -> SquareIndex) -> Self {
+        let mut state = self.clone();
+        for sq in 0..NUM_SQUARES as SquareIndex {
+            let to = f(sq) as usize;
+            state.board[to] = self.board[sq as usize];
+            state.revealed[to] = self.revealed[sq as usize];
+        }
+        for record in state.history.iter_mut() {
+            *record = record.map_squares(f);
+        }
+        for mv in state.redo_stack.iter_mut() {
+            *mv = Move::new(f(mv.from), f(mv.to));
+        }
+        state.refresh();
+        state.rekey_history();
+        state
+    }
+
+    /// Recomputes the key of the position before each move in `history`, by
+    /// taking the moves back on a copy.
+    fn rekey_history(&mut self) {
+        let mut scratch = self.clone();
+        for i in (0..self.history.len()).rev() {
+            scratch.unmake_move();
+            self.history[i].key = scratch.compute_zobrist_key();
+        }
+    }
+
+    /// The position flipped top to bottom. Pieces keep their color, so Red's
+    /// pieces end up in Black's half; combine with
+    /// [`GameState::color_swapped`] to get an equivalent game.
+    pub fn flipped(&self) -> Self {
+        self.map_squares(flip_square)
+    }
+
+    /// The position mirrored left to right.
+    pub fn mirrored(&self) -> Self {
+        self.map_squares(mirror_square)
+    }
+
+    /// The position with every piece, the side to move and any draw offer
+    /// handed to the other color. Pieces stay on their squares.
+    pub fn color_swapped(&self) -> Self {
+        let mut state = self.clone();
+        state.turn = self.turn.other();
+        state.draw_offer = self.draw_offer.map(|c| c.other());
+        for p in state.board.iter_mut().flatten() {
+            *p = swap_color(*p);
+        }
+        for p in state.captured.iter_mut() {
+            *p = swap_color(*p);
+        }
+        for record in state.history.iter_mut() {
+            *record = record.color_swapped();
+        }
+        state.refresh();
+        state.rekey_history();
+        state
+    }
+}
+
+impl Deployment {
+    fn map_squares(&self, color: Color, f: fn(SquareIndex) -> SquareIndex) -> Self {
+        let mut deployment = Deployment::new(color);
+        for (sq, piece) in self.pieces() {
+            deployment.place(f(sq), piece.ty);
+        }
+        deployment
+    }
+
+    /// The deployment flipped top to bottom onto the other half of the board,
+    /// which makes it a deployment for the other color.
+    pub fn flipped(&self) -> Self {
+        self.map_squares(self.color().other(), flip_square)
+    }
+
+    /// The deployment mirrored left to right.
+    pub fn mirrored(&self) -> Self {
+        self.map_squares(self.color(), mirror_square)
+    }
+
+    /// The same deployment played from the other side of the board. Every
+    /// piece keeps its place as seen by its owner, so a Red setup becomes the
+    /// equivalent Black setup and the other way round.
+    pub fn rotated(&self) -> Self {
+        self.flipped().mirrored()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::{sq, SQUARE_TO_SQUARETYPE};
+    use crate::geometry::{ADJACENT, RAIL_LINKS};
+    use crate::prng::PseudoRng;
+    use crate::rules::Rules;
+
+    fn sorted(squares: impl Iterator<Item = SquareIndex>) -> Vec<SquareIndex> {
+        let mut v: Vec<_> = squares.collect();
+        v.sort();
+        v
+    }
+
+    #[test]
+    fn test_square_maps() {
+        let flip = |s: &[SquareIndex]| sorted(s.iter().map(|&sq| flip_square(sq)));
+        assert_eq!(flip(&sq::RED_HQ), sorted(sq::BLACK_HQ.into_iter()));
+        assert_eq!(flip(&sq::RED_SIDE), sorted(sq::BLACK_SIDE.into_iter()));
+        assert_eq!(
+            flip(&sq::RED_FIRST_ROW),
+            sorted(sq::BLACK_FIRST_ROW.into_iter())
+        );
+        assert_eq!(
+            flip(&sq::RED_BACK_ROWS),
+            sorted(sq::BLACK_BACK_ROWS.into_iter())
+        );
+        assert_eq!(flip_square(sq::B1), sq::L1);
+        assert_eq!(mirror_square(sq::B1), sq::B5);
+        assert_eq!(mirror_square(sq::A2), sq::A4);
+
+        for sq in 0..NUM_SQUARES as SquareIndex {
+            for f in [flip_square, mirror_square] {
+                assert_eq!(f(f(sq)), sq);
+                assert_eq!(
+                    SQUARE_TO_SQUARETYPE[f(sq) as usize],
+                    SQUARE_TO_SQUARETYPE[sq as usize]
+                );
+                let map = |links: &[_]| sorted(links.iter().map(|&to| f(to)));
+                assert_eq!(
+                    map(ADJACENT[sq as usize].as_slice()),
+                    sorted(ADJACENT[f(sq) as usize].iter())
+                );
+                assert_eq!(
+                    map(RAIL_LINKS[sq as usize].as_slice()),
+                    sorted(RAIL_LINKS[f(sq) as usize].iter())
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_transformed_positions_are_equivalent() {
+        let state = GameState::random_start(17);
+        let swapped = state.flipped().color_swapped();
+        let mirrored = state.mirrored();
+        assert!(swapped.is_startpos_legal());
+        assert!(mirrored.is_startpos_legal());
+        assert_eq!(swapped.turn, Color::Black);
+        assert_eq!(swapped.flipped().color_swapped().board, state.board);
+
+        let moves = sorted_moves(state.legal_moves().into_iter());
+        let flipped = sorted_moves(swapped.legal_moves().into_iter().map(Move::flipped));
+        let unmirrored = sorted_moves(mirrored.legal_moves().into_iter().map(Move::mirrored));
+        assert_eq!(flipped, moves);
+        assert_eq!(unmirrored, moves);
+    }
+
+    /// Checks that `transform` of the end of a game can be taken back move
+    /// by move through the transformed `snapshots` that led to it.
+    fn check_history(
+        snapshots: &[GameState],
+        end: &GameState,
+        transform: impl Fn(&GameState) -> GameState,
+        map_move: fn(Move) -> Move,
+    ) {
+        let mut transformed = transform(end);
+        // Repetitions are counted the same way on the transformed board
+        assert_eq!(
+            sorted_moves(transformed.allowed_moves().into_iter().map(map_move)),
+            sorted_moves(end.clone().allowed_moves().into_iter())
+        );
+        for snapshot in snapshots.iter().rev() {
+            let record = transformed.unmake_move().unwrap();
+            let expected = transform(snapshot);
+            assert_eq!(transformed.board, expected.board);
+            assert_eq!(transformed.revealed, expected.revealed);
+            assert_eq!(transformed.captured, expected.captured);
+            assert_eq!(transformed.turn, expected.turn);
+            assert_eq!(transformed.history, expected.history);
+            assert_eq!(record.key, expected.zobrist_key());
+            assert_eq!(transformed.zobrist_key(), expected.zobrist_key());
+        }
+    }
+
+    #[test]
+    fn test_transforms_keep_history() {
+        let mut rng = PseudoRng::new(3);
+        let mut state = GameState::random_start(4);
+        let mut snapshots = Vec::new();
+        while snapshots.len() < 40 && !state.result().is_over() {
+            snapshots.push(state.clone());
+            let moves = state.legal_moves();
+            state
+                .make_move(moves[rng.gen_range(0..moves.len())])
+                .unwrap();
+        }
+        assert!(!state.captured.is_empty());
+
+        check_history(
+            &snapshots,
+            &state,
+            |s| s.flipped().color_swapped(),
+            Move::flipped,
+        );
+        check_history(&snapshots, &state, GameState::mirrored, Move::mirrored);
+    }
+
+    fn sorted_moves(moves: impl Iterator<Item = Move>) -> Vec<(SquareIndex, SquareIndex)> {
+        let mut v: Vec<_> = moves.map(|m| (m.from, m.to)).collect();
+        v.sort();
+        v
+    }
+
+    #[test]
+    fn test_deployment_transforms() {
+        let rules = Rules::default();
+        let red = Deployment::random(Color::Red, &rules, &mut PseudoRng::new(8)).unwrap();
+        for transformed in [red.flipped(), red.mirrored(), red.rotated()] {
+            assert_eq!(transformed.validate(&rules), vec![]);
+        }
+        assert_eq!(red.flipped().color(), Color::Black);
+        assert_eq!(red.mirrored().color(), Color::Red);
+        assert_eq!(red.rotated().rotated(), red);
+
+        let black = red.rotated();
+        assert_eq!(red.get(sq::B1), black.get(sq::L5));
+        assert_eq!(red.get(sq::A2), black.get(sq::M4));
+        assert_eq!(red.get(sq::A2), red.flipped().get(sq::M2));
+        assert!(GameState::from_deployments(&red, &black, rules).is_ok());
+    }
+}
@@ -0,0 +1,285 @@
+//! What one player is allowed to know about a game.
+//!
+//! Each player sees its own pieces, but only the color of the opponent's
+//! pieces. Combat is public: a piece that survives an attack is revealed to
+//! both players, and every piece removed from the board is listed in
+//! `captured`. Once a player's field marshal (`PieceType::Overall`) is
+//! destroyed, that player's flag is revealed as well. Every move and the
+//! outcome of every attack are public, and so are the house rules. Clients
+//! and bots should only ever be handed a [`PlayerView`].
+
+use crate::board::{Color, GameState, Piece, PieceType, SquareIndex, NUM_SQUARES};
+use crate::combat::{CombatResult, MoveOutcome};
+use crate::movegen::Move;
+use crate::rules::Rules;
+use serde::{Deserialize, Serialize};
+
+/// A piece as seen by one player. `ty` is `None` for an unknown enemy piece.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ViewPiece {
+    pub ty: Option<PieceType>,
+    pub color: Color,
+}
+
+/// A [`GameState`] projected for one player.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PlayerView {
+    /// The player this view belongs to.
+    pub perspective: Color,
+    pub turn: Color,
+    pub board: Vec<Option<ViewPiece>>,
+    pub captured: Vec<Piece>,
+    pub plies_since_capture: u32,
+    /// Where the opponent's flag is, once it has been revealed.
+    pub opponent_flag: Option<SquareIndex>,
+    /// The rule variants the game is played with.
+    #[serde(default)]
+    pub rules: Rules,
+    /// Every move played so far, oldest first.
+    #[serde(default)]
+    pub history: Vec<ObservedMove>,
+}
+
+/// A move as both players saw it.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ObservedMove {
+    pub mv: Move,
+    pub outcome: MoveOutcome,
+}
+
+impl PlayerView {
+    /// Whether the piece on `sq` is known to this player.
+    pub fn is_known(&self, sq: SquareIndex) -> bool {
+        matches!(self.board[sq as usize], Some(ViewPiece { ty: Some(_), .. }))
+    }
+
+    /// Squares holding an opponent piece whose identity is unknown.
+    pub fn hidden_squares(&self) -> Vec<SquareIndex> {
+        (0..NUM_SQUARES as SquareIndex)
+            .filter(|&sq| matches!(self.board[sq as usize], Some(ViewPiece { ty: None, .. })))
+            .collect()
+    }
+
+    /// The moves made by the piece now on `sq`, oldest first, found by
+    /// following it back through `history`.
+    pub fn piece_moves(&self, sq: SquareIndex) -> Vec<Move> {
+        let mut square = sq;
+        let mut moves = Vec::new();
+        for observed in self.history.iter().rev() {
+            if observed.mv.to != square {
+                continue;
+            }
+            // Only the piece that ends up on the target square matters: the
+            // mover, unless the defender held its ground
+            let mover_stayed = match observed.outcome {
+                MoveOutcome::Moved | MoveOutcome::FlagCaptured { .. } => true,
+                MoveOutcome::Attack { result, .. } => result == CombatResult::AttackerWins,
+            };
+            if mover_stayed {
+                moves.push(observed.mv);
+                square = observed.mv.from;
+            }
+        }
+        moves.reverse();
+        moves
+    }
+
+    /// The square the piece on `sq` started the game on, and whether it has
+    /// moved since.
+    pub fn origin(&self, sq: SquareIndex) -> (SquareIndex, bool) {
+        let moves = self.piece_moves(sq);
+        (moves.first().map_or(sq, |m| m.from), !moves.is_empty())
+    }
+}
+
+impl GameState {
+    /// Returns the square of `color`'s flag, if it is still on the board.
+    pub fn flag_square(&self, color: Color) -> Option<SquareIndex> {
+        self.board
+            .iter()
+            .position(|&p| {
+                p == Some(Piece {
+                    ty: PieceType::Flag,
+                    color,
+                })
+            })
+            .map(|i| i as SquareIndex)
+    }
+
+    /// Whether `color`'s flag has been revealed to the opponent.
+    pub fn flag_revealed(&self, color: Color) -> bool {
+        self.flag_square(color)
+            .is_some_and(|sq| self.revealed[sq as usize])
+    }
+
+    /// Makes the location of `color`'s flag public.
+    pub(crate) fn reveal_flag(&mut self, color: Color) {
+        if let Some(sq) = self.flag_square(color) {
+            self.revealed[sq as usize] = true;
+        }
+    }
+
+    /// Projects the game for `perspective`, hiding the opponent's unrevealed pieces.
+    pub fn view(&self, perspective: Color) -> PlayerView {
+        let board = self
+            .board
+            .iter()
+            .zip(&self.revealed)
+            .map(|(opt, &revealed)| {
+                opt.map(|p| ViewPiece {
+                    ty: (p.color == perspective || revealed).then_some(p.ty),
+                    color: p.color,
+                })
+            })
+            .collect();
+
+        PlayerView {
+            perspective,
+            turn: self.turn,
+            board,
+            captured: self.captured.clone(),
+            plies_since_capture: self.plies_since_capture,
+            opponent_flag: self
+                .flag_square(perspective.other())
+                .filter(|&sq| self.revealed[sq as usize]),
+            rules: self.rules,
+            history: self
+                .history
+                .iter()
+                .map(|r| ObservedMove {
+                    mv: r.mv,
+                    outcome: r.outcome,
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::sq;
+    use crate::movegen::Move;
+
+    #[test]
+    fn test_opponent_pieces_are_hidden() {
+        let state = GameState::random_start(5);
+        let view = state.view(Color::Red);
+        for i in 0..NUM_SQUARES {
+            match (state.board[i], view.board[i]) {
+                (None, None) => {}
+                (Some(p), Some(v)) => {
+                    assert_eq!(p.color, v.color);
+                    if p.color == Color::Red {
+                        assert_eq!(v.ty, Some(p.ty));
+                    } else {
+                        assert_eq!(v.ty, None);
+                    }
+                }
+                _ => panic!("occupancy differs on square {i}"),
+            }
+        }
+        assert_eq!(view.hidden_squares().len(), 25);
+    }
+
+    #[test]
+    fn test_combat_reveals_survivor() {
+        let mut state = GameState::new();
+        state.set_piece(
+            sq::F1,
+            Some(Piece {
+                ty: PieceType::Division,
+                color: Color::Red,
+            }),
+        );
+        state.set_piece(
+            sq::H1,
+            Some(Piece {
+                ty: PieceType::Brigade,
+                color: Color::Black,
+            }),
+        );
+        state.set_piece(
+            sq::L1,
+            Some(Piece {
+                ty: PieceType::Army,
+                color: Color::Black,
+            }),
+        );
+        state.make_move(Move::new(sq::F1, sq::H1)).unwrap();
+
+        let view = state.view(Color::Black);
+        assert!(view.is_known(sq::H1));
+        assert_eq!(
+            view.board[sq::H1 as usize].unwrap().ty,
+            Some(PieceType::Division)
+        );
+        assert_eq!(
+            view.captured,
+            vec![Piece {
+                ty: PieceType::Brigade,
+                color: Color::Black
+            }]
+        );
+
+        // The revealed piece stays revealed when it moves on
+        state.make_move(Move::new(sq::L1, sq::L2)).unwrap();
+        state.make_move(Move::new(sq::H1, sq::I1)).unwrap();
+        assert!(state.view(Color::Black).is_known(sq::I1));
+        assert!(!state.view(Color::Red).is_known(sq::L2));
+
+        // Both players can follow each piece back to where it started
+        let view = state.view(Color::Red);
+        assert_eq!(view.history.len(), 3);
+        assert_eq!(view.origin(sq::I1), (sq::F1, true));
+        assert_eq!(view.origin(sq::L2), (sq::L1, true));
+        assert_eq!(view.origin(sq::A1), (sq::A1, false));
+        assert_eq!(
+            view.piece_moves(sq::I1),
+            vec![Move::new(sq::F1, sq::H1), Move::new(sq::H1, sq::I1)]
+        );
+    }
+
+    #[test]
+    fn test_flag_revealed_when_overall_dies() {
+        let mut state = GameState::random_start(11);
+        let flag = state.flag_square(Color::Black).unwrap();
+        assert_eq!(state.view(Color::Red).opponent_flag, None);
+
+        // Put Red's bomb next to Black's field marshal and trade them
+        let overall = state
+            .board
+            .iter()
+            .position(|&p| {
+                p == Some(Piece {
+                    ty: PieceType::Overall,
+                    color: Color::Black,
+                })
+            })
+            .unwrap() as SquareIndex;
+        state.set_piece(overall, None);
+        state.set_piece(
+            sq::H1,
+            Some(Piece {
+                ty: PieceType::Overall,
+                color: Color::Black,
+            }),
+        );
+        state.set_piece(sq::G1, None);
+        state.set_piece(
+            sq::F1,
+            Some(Piece {
+                ty: PieceType::Bomb,
+                color: Color::Red,
+            }),
+        );
+        state.make_move(Move::new(sq::F1, sq::H1)).unwrap();
+
+        assert!(state.flag_revealed(Color::Black));
+        assert!(!state.flag_revealed(Color::Red));
+        let view = state.view(Color::Red);
+        assert_eq!(view.opponent_flag, Some(flag));
+        assert_eq!(view.board[flag as usize].unwrap().ty, Some(PieceType::Flag));
+        assert_eq!(state.view(Color::Black).opponent_flag, None);
+    }
+}
@@ -0,0 +1,153 @@
+//! Zobrist keys for positions.
+//!
+//! Every combination of piece type, color and square has a fixed random
+//! 64-bit key, and so does Black being on move. The key of a position is the
+//! XOR of the keys of all pieces on the board, plus the side key when Black is
+//! to move, so a move changes it by XORing in only the squares it touches.
+//! Keys are drawn from [`PseudoRng`] with a fixed seed and are the same in
+//! every build.
+//!
+//! Revealed squares, captured pieces and the move counters are not part of
+//! the key.
+
+use crate::board::{Color, GameState, Piece, SquareIndex, NUM_PIECETYPES, NUM_SQUARES};
+use crate::prng::PseudoRng;
+use std::sync::OnceLock;
+
+const SEED: u64 = 0x4c75_7a68_616e_7169;
+
+struct ZobristKeys {
+    pieces: [[[u64; NUM_SQUARES]; 2]; NUM_PIECETYPES],
+    black_to_move: u64,
+}
+
+fn keys() -> &'static ZobristKeys {
+    static KEYS: OnceLock<ZobristKeys> = OnceLock::new();
+    KEYS.get_or_init(|| {
+        let mut rng = PseudoRng::new(SEED);
+        let mut pieces = [[[0; NUM_SQUARES]; 2]; NUM_PIECETYPES];
+        for key in pieces.iter_mut().flatten().flatten() {
+            *key = rng.next_u64();
+        }
+        ZobristKeys {
+            pieces,
+            black_to_move: rng.next_u64(),
+        }
+    })
+}
+
+/// The key for `piece` standing on `sq`.
+pub fn piece_key(piece: Piece, sq: SquareIndex) -> u64 {
+    keys().pieces[piece.ty as usize][piece.color as usize][sq as usize]
+}
+
+/// The key XORed in while Black is to move.
+pub fn side_key() -> u64 {
+    keys().black_to_move
+}
+
+/// The key for the contents of `sq`, zero if it is empty.
+pub(crate) fn square_key(sq: SquareIndex, piece: Option<Piece>) -> u64 {
+    piece.map_or(0, |p| piece_key(p, sq))
+}
+
+impl GameState {
+    /// The Zobrist key of the position.
+    pub fn zobrist_key(&self) -> u64 {
+        self.zobrist
+    }
+
+    /// Computes the Zobrist key from scratch.
+    pub fn compute_zobrist_key(&self) -> u64 {
+        let mut key = if self.turn == Color::Black {
+            side_key()
+        } else {
+            0
+        };
+        for (sq, &piece) in self.board.iter().enumerate() {
+            key ^= square_key(sq as SquareIndex, piece);
+        }
+        key
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::{sq, PieceType};
+    use crate::movegen::Move;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_keys_are_distinct() {
+        let mut seen = HashSet::new();
+        for key in keys().pieces.iter().flatten().flatten() {
+            assert!(seen.insert(*key));
+        }
+        assert!(seen.insert(side_key()));
+        assert!(!seen.contains(&0));
+    }
+
+    #[test]
+    fn test_incremental_key_matches_full() {
+        for seed in 0..10 {
+            let mut state = GameState::random_start(seed);
+            let start = state.zobrist_key();
+            assert_eq!(start, state.compute_zobrist_key());
+            for i in 0..150 {
+                let moves = state.legal_moves();
+                if moves.is_empty() || state.result().is_over() {
+                    break;
+                }
+                state.make_move_unchecked(moves[(seed as usize + i * 13) % moves.len()]);
+                assert_eq!(state.zobrist_key(), state.compute_zobrist_key());
+            }
+            while state.unmake_move().is_some() {
+                assert_eq!(state.zobrist_key(), state.compute_zobrist_key());
+            }
+            assert_eq!(state.zobrist_key(), start);
+        }
+    }
+
+    #[test]
+    fn test_same_position_same_key() {
+        let mut state = GameState::new();
+        state.set_piece(
+            sq::F1,
+            Some(Piece {
+                ty: PieceType::Army,
+                color: Color::Red,
+            }),
+        );
+        state.set_piece(
+            sq::L5,
+            Some(Piece {
+                ty: PieceType::Army,
+                color: Color::Black,
+            }),
+        );
+        state.refresh();
+        let start = state.zobrist_key();
+
+        for mv in [
+            Move::new(sq::F1, sq::E1),
+            Move::new(sq::L5, sq::K5),
+            Move::new(sq::E1, sq::F1),
+        ] {
+            state.make_move(mv).unwrap();
+            assert_ne!(state.zobrist_key(), start);
+        }
+        state.make_move(Move::new(sq::K5, sq::L5)).unwrap();
+        assert_eq!(state.zobrist_key(), start);
+
+        let parsed = GameState::from_notation(&state.to_notation()).unwrap();
+        assert_eq!(parsed.zobrist_key(), start);
+        let json = serde_json::to_string(&state).unwrap();
+        let restored: GameState = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.zobrist_key(), start);
+
+        state.set_turn(Color::Black);
+        assert_eq!(state.zobrist_key(), state.compute_zobrist_key());
+        assert_ne!(state.zobrist_key(), start);
+    }
+}
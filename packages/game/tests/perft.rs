@@ -0,0 +1,62 @@
+//! Perft counts for fixed positions, stored in `fixtures/perft.json`.
+//!
+//! Each fixture gives a position, either as a `random_start` seed or in
+//! position notation, and the expected `perft` count for depths 1, 2, ...
+//! A change in any count means move generation changed.
+
+use game::board::GameState;
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+struct Fixture {
+    name: String,
+    seed: Option<u64>,
+    notation: Option<String>,
+    counts: Vec<u64>,
+}
+
+impl Fixture {
+    fn state(&self) -> GameState {
+        match (self.seed, &self.notation) {
+            (Some(seed), None) => GameState::random_start(seed),
+            (None, Some(notation)) => {
+                GameState::from_notation(notation).unwrap_or_else(|e| panic!("{}: {e}", self.name))
+            }
+            _ => panic!("{}: give exactly one of seed and notation", self.name),
+        }
+    }
+}
+
+fn fixtures() -> Vec<Fixture> {
+    serde_json::from_str(include_str!("fixtures/perft.json")).expect("invalid perft fixtures")
+}
+
+#[test]
+fn perft_counts() {
+    for fixture in fixtures() {
+        let state = fixture.state();
+        for (depth, &expected) in (1..).zip(&fixture.counts) {
+            assert_eq!(
+                state.perft(depth),
+                expected,
+                "{} at depth {depth}",
+                fixture.name
+            );
+        }
+    }
+}
+
+#[test]
+fn divide_sums_to_perft() {
+    for fixture in fixtures() {
+        let state = fixture.state();
+        let depth = fixture.counts.len() as u32;
+        let total: u64 = state.divide(depth).iter().map(|&(_, n)| n).sum();
+        assert_eq!(
+            total,
+            fixture.counts[depth as usize - 1],
+            "{}",
+            fixture.name
+        );
+    }
+}
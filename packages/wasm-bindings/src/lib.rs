@@ -1,29 +1,192 @@
+use engine::ismcts::IsmctsConfig;
+use engine::limits::{Clock, SearchLimits};
 use engine::Engine;
-use game::board::GameState;
-use serde_wasm_bindgen::to_value;
+use game::board::{Color, GameState, SquareType, SQUARE_TO_SQUARETYPE};
+use game::geometry::{Links, ADJACENT, RAIL_LINKS};
+use game::movegen::Move;
+use game::rules::Rules;
+use game::setup::Deployment;
+use serde::Serialize;
+use serde_wasm_bindgen::{from_value, to_value};
 use wasm_bindgen::prelude::*;
 
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(js_namespace = Date)]
+    fn now() -> f64;
+}
+
+/// The browser clock, as `std::time::Instant` is not available in WASM.
+struct DateClock;
+
+impl Clock for DateClock {
+    fn now_ms(&self) -> u64 {
+        now() as u64
+    }
+}
+
+fn engine_with(state: GameState) -> WasmEngine {
+    let mut inner = Engine::with_state(state);
+    inner.set_clock(Box::new(DateClock));
+    WasmEngine { inner }
+}
+
 #[wasm_bindgen]
 pub struct WasmEngine {
     inner: Engine,
 }
 
+impl Default for WasmEngine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[wasm_bindgen]
 impl WasmEngine {
+    /// An engine with an empty board.
     #[wasm_bindgen(constructor)]
     pub fn new() -> WasmEngine {
-        WasmEngine {
-            inner: Engine::new(),
+        engine_with(GameState::new())
+    }
+
+    /// Starts a game from both players' deployments, played with `rules`
+    /// or the standard rules if left out. Throws the list of setup
+    /// violations if the deployments do not make a legal start.
+    #[wasm_bindgen(js_name = fromDeployments)]
+    pub fn from_deployments(
+        red: JsValue,
+        black: JsValue,
+        rules: JsValue,
+    ) -> Result<WasmEngine, JsValue> {
+        let red: Deployment = from_value(red)?;
+        let black: Deployment = from_value(black)?;
+        let rules: Option<Rules> = from_value(rules)?;
+        match GameState::from_deployments(&red, &black, rules.unwrap_or_default()) {
+            Ok(state) => Ok(engine_with(state)),
+            Err(violations) => Err(to_value(&violations)?),
         }
     }
 
-    #[wasm_bindgen(js_name = getState)]
-    pub fn get_state(&self) -> JsValue {
-        to_value(self.inner.state()).unwrap()
+    /// Loads a position written by `toNotation`.
+    #[wasm_bindgen(js_name = fromNotation)]
+    pub fn from_notation(notation: &str) -> Result<WasmEngine, JsValue> {
+        GameState::from_notation(notation)
+            .map(engine_with)
+            .map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+
+    #[wasm_bindgen(js_name = toNotation)]
+    pub fn to_notation(&self) -> String {
+        self.inner.state().to_notation()
     }
+
+    /// Plays `mv`, an object with `from` and `to` square indices, and
+    /// returns what happened. Throws if the move is not allowed.
+    #[wasm_bindgen(js_name = makeMove)]
+    pub fn make_move(&mut self, mv: JsValue) -> Result<JsValue, JsValue> {
+        let mv: Move = from_value(mv)?;
+        let outcome = self
+            .inner
+            .state_mut()
+            .make_move(mv)
+            .map_err(|e| JsValue::from_str(&e.to_string()))?;
+        Ok(to_value(&outcome)?)
+    }
+
+    /// Takes back the last move. Returns `false` if there is none.
+    pub fn undo(&mut self) -> bool {
+        self.inner.state_mut().undo().is_some()
+    }
+
+    /// The moves the side to move may play.
+    #[wasm_bindgen(js_name = allowedMoves)]
+    pub fn allowed_moves(&mut self) -> Result<JsValue, JsValue> {
+        Ok(to_value(&self.inner.state_mut().allowed_moves())?)
+    }
+
+    /// Whether the game is still going, and if not, who won and why.
+    pub fn result(&self) -> Result<JsValue, JsValue> {
+        Ok(to_value(&self.inner.state().result())?)
+    }
+
+    /// The game as seen by `perspective` (`"Red"` or `"Black"`).
+    #[wasm_bindgen(js_name = getView)]
+    pub fn get_view(&self, perspective: JsValue) -> Result<JsValue, JsValue> {
+        let perspective: Color = from_value(perspective)?;
+        Ok(to_value(&self.inner.state().view(perspective))?)
+    }
+
+    /// What `perspective` can infer about each hidden opponent piece, for a
+    /// likely-piece overlay.
+    #[wasm_bindgen(js_name = getBeliefs)]
+    pub fn get_beliefs(&self, perspective: JsValue) -> Result<JsValue, JsValue> {
+        let perspective: Color = from_value(perspective)?;
+        Ok(to_value(&self.inner.beliefs(perspective))?)
+    }
+
+    /// Searches the current position within `limits`, an object with any of
+    /// `depth`, `nodes`, `move_time_ms`, `time_left_ms` and `increment_ms`.
+    pub fn search(&mut self, limits: JsValue) -> Result<JsValue, JsValue> {
+        let limits: SearchLimits = from_value(limits)?;
+        Ok(to_value(&self.inner.search_with(&limits))?)
+    }
+
+    /// Searches for a move for the side to move using only what
+    /// `perspective` can see, stopping within the time `limits` allow.
+    /// `config` may set any of `iterations`, `max_playout_plies`,
+    /// `exploration` and `seed`; positions are scored with the weights given
+    /// to `setEvalWeights`.
+    #[wasm_bindgen(js_name = searchHidden)]
+    pub fn search_hidden(
+        &self,
+        perspective: JsValue,
+        limits: JsValue,
+        config: JsValue,
+    ) -> Result<JsValue, JsValue> {
+        let perspective: Color = from_value(perspective)?;
+        let limits: SearchLimits = from_value(limits)?;
+        let config = IsmctsConfig {
+            weights: *self.inner.weights(),
+            ..from_value::<Option<IsmctsConfig>>(config)?.unwrap_or_default()
+        };
+        let result = self.inner.search_hidden_with(perspective, &config, &limits);
+        Ok(to_value(&result)?)
+    }
+
+    /// Replaces the evaluation weights. Fields left out keep their defaults.
+    #[wasm_bindgen(js_name = setEvalWeights)]
+    pub fn set_eval_weights(&mut self, weights: JsValue) -> Result<(), JsValue> {
+        self.inner.set_weights(from_value(weights)?);
+        Ok(())
+    }
+}
+
+/// A random starting position as seen by `perspective` (`"Red"` or `"Black"`).
+#[wasm_bindgen]
+pub fn random_start(seed: u64, perspective: JsValue) -> Result<JsValue, JsValue> {
+    let perspective: Color = from_value(perspective)?;
+    Ok(to_value(&GameState::random_start(seed).view(perspective))?)
+}
+
+/// Square types and connection tables, so the UI does not hard-code them.
+#[derive(Serialize)]
+struct BoardGeometry {
+    square_types: &'static [SquareType],
+    adjacent: Vec<Vec<u8>>,
+    rail_links: Vec<Vec<u8>>,
+}
+
+fn links_to_vec(table: &[Links]) -> Vec<Vec<u8>> {
+    table.iter().map(|l| l.as_slice().to_vec()).collect()
 }
 
 #[wasm_bindgen]
-pub fn random_start(seed: u64) -> JsValue {
-    to_value(&GameState::random_start(seed)).unwrap()
+pub fn board_geometry() -> JsValue {
+    to_value(&BoardGeometry {
+        square_types: &SQUARE_TO_SQUARETYPE,
+        adjacent: links_to_vec(&ADJACENT),
+        rail_links: links_to_vec(&RAIL_LINKS),
+    })
+    .unwrap()
 }